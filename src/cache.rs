@@ -0,0 +1,58 @@
+use crate::lyrics::{normalize_name, Lyrics, Query};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+// another version of the song (radio edit, live) if the lengths differ more
+const SAME_TRACK_MS: u64 = 3000;
+// plain lyrics are looked up again after this, in case synced ones appeared
+const PLAIN_TTL: Duration = Duration::from_secs(7 * 24 * 3600);
+
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    pub artist: String,
+    pub title: String,
+    pub album: String,
+    pub duration_ms: u64,
+    pub lyrics: Lyrics,
+}
+
+pub fn dir() -> PathBuf {
+    crate::config::cache_dir().join("lyrics")
+}
+
+// file names only keep alphanumerics so "AC/DC" and "ac dc" share an entry
+fn path(artist: &str, title: &str) -> PathBuf {
+    dir().join(format!("{}-{}.json", normalize_name(artist), normalize_name(title)))
+}
+
+pub fn load(q: &Query) -> Option<Lyrics> {
+    let path = path(&q.artist, &q.title);
+    let contents = std::fs::read_to_string(&path).ok()?;
+    let entry: Entry = serde_json::from_str(&contents).ok()?;
+    let lengths_known = q.duration_ms > 0 && entry.duration_ms > 0;
+    if lengths_known && q.duration_ms.abs_diff(entry.duration_ms) > SAME_TRACK_MS {
+        return None;
+    }
+    if !entry.lyrics.synced {
+        let age = std::fs::metadata(&path).and_then(|m| m.modified()).ok()?.elapsed().ok()?;
+        if age > PLAIN_TTL {
+            return None;
+        }
+    }
+    Some(entry.lyrics)
+}
+
+pub fn store(q: &Query, lyrics: &Lyrics) {
+    let entry = Entry {
+        artist: q.artist.clone(),
+        title: q.title.clone(),
+        album: q.album.clone(),
+        duration_ms: q.duration_ms,
+        lyrics: lyrics.clone(),
+    };
+    std::fs::create_dir_all(dir()).ok();
+    if let Ok(json) = serde_json::to_string(&entry) {
+        std::fs::write(path(&q.artist, &q.title), json).ok();
+    }
+}
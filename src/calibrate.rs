@@ -0,0 +1,243 @@
+use crate::lyrics::{self, LyricLine, Lyrics, Query};
+use crate::player::{Control, PlayerBackend};
+use crate::spotify::{self, fmt_time, NowPlaying};
+use crate::{client, play};
+use crossterm::event::{self, Event, KeyCode};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use rspotify::AuthCodeSpotify;
+use std::io::Write;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Rick Astley - Never Gonna Give You Up: steady, well-known line starts
+const REFERENCE_TRACK: &str = "spotify:track:4uLU6hMCjMI75M1A2tKUQC";
+const TAPS: usize = 12;
+const MIN_TAPS: usize = 4;
+// taps further than this from every line start are treated as mistakes
+const MAX_MISS_MS: i64 = 1500;
+// start playback a little before the first line
+const LEAD_MS: u64 = 5000;
+// taps on a visual cue first, to take the user's reaction time out of the lag
+const REACTION_TAPS: usize = 5;
+// slower than this is a missed cue, not a reaction
+const MAX_REACTION_MS: u128 = 1000;
+// typical reaction to a cue, for when the measurement is skipped
+const DEFAULT_REACTION_MS: i64 = 200;
+
+async fn wait_for_track(spotify: &AuthCodeSpotify, id: &str) -> Result<NowPlaying, String> {
+    let deadline = Instant::now() + Duration::from_secs(15);
+    loop {
+        if let Some(np) = spotify::now_playing(spotify).await
+            && np.id.as_deref() == Some(id)
+        {
+            return Ok(np);
+        }
+        if Instant::now() > deadline {
+            return Err("timed out waiting for the track to start".into());
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+async fn synced_lyrics(np: &NowPlaying) -> Result<Lyrics, String> {
+    let query = Query {
+        artist: np.artist.clone(),
+        title: np.track.clone(),
+        album: np.album.clone(),
+        duration_ms: np.duration_ms,
+    };
+    lyrics::fetch(&query)
+        .await
+        .filter(|ly| ly.synced)
+        .ok_or_else(|| "no synced lyrics for this track; pick another with --track".into())
+}
+
+// playback position read once after the seek and then counted on locally,
+// so a tap doesn't wait on a request
+struct Clock {
+    base_ms: u64,
+    at: Instant,
+}
+
+impl Clock {
+    async fn read(spotify: &AuthCodeSpotify) -> Result<Self, String> {
+        let np = spotify::now_playing(spotify).await.ok_or("playback stopped")?;
+        Ok(Self { base_ms: np.progress_ms, at: Instant::now() })
+    }
+
+    fn position_ms(&self) -> u64 {
+        self.base_ms + self.at.elapsed().as_millis() as u64
+    }
+}
+
+// signed distance from the playback position to the closest line start
+fn nearest_delta(lines: &[LyricLine], pos_ms: u64) -> Option<i64> {
+    lines
+        .iter()
+        .filter(|l| !l.text.trim().is_empty())
+        .map(|l| pos_ms as i64 - l.time_ms as i64)
+        .min_by_key(|d| d.abs())
+}
+
+fn collect_taps(lines: &[LyricLine], clock: &Clock) -> Result<Vec<i64>, String> {
+    enable_raw_mode().map_err(|e| e.to_string())?;
+    let mut deltas = Vec::new();
+    while deltas.len() < TAPS {
+        if !event::poll(Duration::from_millis(100)).unwrap_or(false) {
+            continue;
+        }
+        let Ok(Event::Key(key)) = event::read() else { continue };
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => break,
+            KeyCode::Char(' ') | KeyCode::Enter => {
+                let pos = clock.position_ms();
+                match nearest_delta(lines, pos) {
+                    Some(d) if d.abs() <= MAX_MISS_MS => {
+                        deltas.push(d);
+                        print!("  tap {:>2}: {d:+}ms\r\n", deltas.len());
+                    }
+                    _ => print!("  ignored, no line starts near {}\r\n", fmt_time(pos)),
+                }
+            }
+            _ => {}
+        }
+    }
+    disable_raw_mode().map_err(|e| e.to_string())?;
+    Ok(deltas)
+}
+
+enum Tap {
+    Tapped,
+    Quit,
+    TimedOut,
+}
+
+// waits for space or Enter, or q/Esc, for up to `timeout` if given
+fn wait_tap(timeout: Option<Duration>) -> Tap {
+    let deadline = timeout.map(|t| Instant::now() + t);
+    loop {
+        let left = match deadline {
+            Some(d) => d.saturating_duration_since(Instant::now()),
+            None => Duration::from_millis(100),
+        };
+        if !event::poll(left).unwrap_or(false) {
+            if deadline.is_some() {
+                return Tap::TimedOut;
+            }
+            continue;
+        }
+        let Ok(Event::Key(key)) = event::read() else { continue };
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Tap::Quit,
+            KeyCode::Char(' ') | KeyCode::Enter => return Tap::Tapped,
+            _ => {}
+        }
+    }
+}
+
+// median delay between a cue appearing and the tap for it, with no audio
+// involved; every tap on a line start has this in it too
+fn measure_reaction() -> Result<Option<i64>, String> {
+    enable_raw_mode().map_err(|e| e.to_string())?;
+    let mut times = Vec::new();
+    while times.len() < REACTION_TAPS {
+        // an uneven wait, so the cue can't be anticipated
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.subsec_nanos());
+        let wait = Duration::from_millis(1000 + nanos as u64 % 1500);
+        match wait_tap(Some(wait)) {
+            Tap::Quit => break,
+            Tap::Tapped => {
+                print!("  too early\r\n");
+                continue;
+            }
+            Tap::TimedOut => {}
+        }
+        print!("  \u{25cf} tap!");
+        let _ = std::io::stdout().flush();
+        let shown = Instant::now();
+        if let Tap::Quit = wait_tap(None) {
+            break;
+        }
+        let ms = shown.elapsed().as_millis();
+        if ms <= MAX_REACTION_MS {
+            times.push(ms as i64);
+            print!(" {ms}ms\r\n");
+        } else {
+            print!(" missed\r\n");
+        }
+    }
+    disable_raw_mode().map_err(|e| e.to_string())?;
+    Ok((times.len() >= REACTION_TAPS.div_ceil(2)).then(|| median(&times)))
+}
+
+fn median(values: &[i64]) -> i64 {
+    let mut values = values.to_vec();
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2
+    } else {
+        values[mid]
+    }
+}
+
+// saved in the daemon, which also applies it right away if that device is
+// the one on screen
+fn save_offset(device: &str, ms: i64) -> Result<(), String> {
+    let arg = format!("{ms} {device}");
+    client::send_command(&serde_json::json!({"cmd": "device_offset", "arg": arg}).to_string())?;
+    Ok(())
+}
+
+/// Plays a reference track on Spotify, has the user tap along with the line
+/// starts and saves the median lag as the active Spotify device's latency
+/// offset.
+pub async fn run(spotify: &AuthCodeSpotify, track: Option<&str>, reset: bool) -> Result<(), String> {
+    let device = spotify::now_playing(spotify)
+        .await
+        .and_then(|n| n.device)
+        .ok_or("no active Spotify device; start playback on the device you want to calibrate")?;
+
+    if reset {
+        save_offset(&device.name, 0)?;
+        println!("cleared latency offset for {}", device.name);
+        return Ok(());
+    }
+
+    println!("calibrating {} ({})", device.name, device.device_type);
+    println!("first, press space as soon as each \u{25cf} appears ({REACTION_TAPS} taps, q to skip)");
+    let reaction = measure_reaction()?.unwrap_or_else(|| {
+        println!("not enough taps; assuming {DEFAULT_REACTION_MS}ms reaction time");
+        DEFAULT_REACTION_MS
+    });
+
+    let id = crate::sync::parse_id(track.unwrap_or(REFERENCE_TRACK), "track");
+    play::play_uri(spotify, &id, None).await?;
+    let np = wait_for_track(spotify, &id).await?;
+    let lines = synced_lyrics(&np).await?.lines;
+    let first = lines.iter().find(|l| !l.text.trim().is_empty()).map_or(0, |l| l.time_ms);
+    let start = first.saturating_sub(LEAD_MS);
+    spotify.control(Control::Seek(start)).await?;
+    // Spotify may still report the old position right after a seek
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    let clock = Clock::read(spotify).await?;
+
+    println!("press space the moment you hear each new line start ({TAPS} taps, q to stop early)");
+    let deltas = collect_taps(&lines, &clock)?;
+    if deltas.len() < MIN_TAPS {
+        return Err(format!("only {} taps, need at least {MIN_TAPS}", deltas.len()));
+    }
+
+    // taps land a reaction time after the line starts wherever it plays; the
+    // rest is the device. A positive lag means the lyrics show early, so
+    // shift them later
+    let tapped = median(&deltas);
+    let lag = tapped - reaction;
+    let offset = -lag;
+    save_offset(&device.name, offset)?;
+    println!(
+        "median tap {tapped:+}ms over {} taps, less {reaction}ms reaction time",
+        deltas.len()
+    );
+    println!("lag {lag:+}ms; saved offset {offset:+}ms for {}", device.name);
+    Ok(())
+}
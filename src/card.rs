@@ -0,0 +1,160 @@
+use crate::draw::{self, Align, Fonts, Palette};
+use crate::{client, quotes};
+use image::{ImageFormat, RgbaImage};
+
+#[derive(Clone, Copy)]
+pub enum Preset {
+    Square,
+    Story,
+    Wide,
+}
+
+impl Preset {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "square" => Some(Self::Square),
+            "story" | "portrait" => Some(Self::Story),
+            "wide" | "landscape" => Some(Self::Wide),
+            _ => None,
+        }
+    }
+}
+
+pub struct Card {
+    pub lines: Vec<String>,
+    pub artist: String,
+    pub title: String,
+    pub art_url: Option<String>,
+}
+
+struct Layout {
+    width: u32,
+    height: u32,
+    // x, y, size
+    art: Option<(u32, u32, u32)>,
+    // x, y, width, height
+    text: (f32, f32, f32, f32),
+    align: Align,
+    lyric_px: f32,
+    meta_px: f32,
+}
+
+fn layout(preset: Preset, has_art: bool) -> Layout {
+    let mut l = match preset {
+        Preset::Square => Layout {
+            width: 1080,
+            height: 1080,
+            art: Some((300, 90, 480)),
+            text: (90.0, 620.0, 900.0, 400.0),
+            align: Align::Center,
+            lyric_px: 54.0,
+            meta_px: 32.0,
+        },
+        Preset::Story => Layout {
+            width: 1080,
+            height: 1920,
+            art: Some((140, 220, 800)),
+            text: (90.0, 1100.0, 900.0, 640.0),
+            align: Align::Center,
+            lyric_px: 68.0,
+            meta_px: 38.0,
+        },
+        Preset::Wide => Layout {
+            width: 1600,
+            height: 900,
+            art: Some((100, 150, 600)),
+            text: (780.0, 100.0, 720.0, 700.0),
+            align: Align::Left,
+            lyric_px: 56.0,
+            meta_px: 32.0,
+        },
+    };
+    if !has_art {
+        l.art = None;
+        l.text = (90.0, 90.0, l.width as f32 - 180.0, l.height as f32 - 180.0);
+        l.align = Align::Center;
+    }
+    l
+}
+
+pub fn render(card: &Card, preset: Preset) -> RgbaImage {
+    let fonts = Fonts::load();
+    let art = card.art_url.as_deref().and_then(draw::load_art);
+    let palette = art.as_ref().map(Palette::from_art).unwrap_or_default();
+    let l = layout(preset, art.is_some());
+
+    let mut img = RgbaImage::new(l.width, l.height);
+    draw::fill_gradient(&mut img, palette.top, palette.bottom);
+    if let (Some(art), Some((x, y, size))) = (&art, l.art) {
+        draw::paste_art(&mut img, art, x, y, size);
+    }
+
+    // shrink the text until lyric and credit fit the box
+    let (bx, by, bw, bh) = l.text;
+    let meta = format!("{} \u{2014} {}", card.artist, card.title);
+    let (mut lyric_px, mut meta_px) = (l.lyric_px, l.meta_px);
+    let (rows, meta_rows, total) = loop {
+        let rows: Vec<String> = card
+            .lines
+            .iter()
+            .flat_map(|line| draw::wrap(&fonts.bold, lyric_px, line, bw))
+            .collect();
+        let meta_rows = draw::wrap(&fonts.regular, meta_px, &meta, bw);
+        let total = rows.len() as f32 * draw::line_height(&fonts.bold, lyric_px)
+            + lyric_px * 0.8
+            + meta_rows.len() as f32 * draw::line_height(&fonts.regular, meta_px);
+        if total <= bh || lyric_px <= 20.0 {
+            break (rows, meta_rows, total);
+        }
+        lyric_px *= 0.9;
+        meta_px = (meta_px * 0.9).max(18.0);
+    };
+
+    let y = by + ((bh - total) / 2.0).max(0.0);
+    let y = draw::lines(&mut img, &fonts.bold, lyric_px, bx, y, bw, l.align, &rows, palette.text);
+    draw::lines(
+        &mut img,
+        &fonts.regular,
+        meta_px,
+        bx,
+        y + lyric_px * 0.8,
+        bw,
+        l.align,
+        &meta_rows,
+        palette.dim,
+    );
+    img
+}
+
+fn current(offset_ms: i64) -> Result<Card, String> {
+    let state = client::fetch_state();
+    let np = state.estimate().ok_or("nothing playing right now")?;
+    let idx = client::current_index(&state, &np, offset_ms)
+        .ok_or("no current line (lyrics missing, unsynced or not started yet)")?;
+    let line = state.lyrics.as_ref().map(|ly| ly.lines[idx].text.clone()).unwrap_or_default();
+    if line.trim().is_empty() {
+        return Err("the current line is empty".into());
+    }
+    Ok(Card { lines: vec![line], artist: np.artist, title: np.track, art_url: np.album_art })
+}
+
+fn saved_quote(n: usize) -> Result<Card, String> {
+    let q = n
+        .checked_sub(1)
+        .and_then(|i| quotes::load().into_iter().nth(i))
+        .ok_or_else(|| format!("no quote #{n} (see `lirik quotes`)"))?;
+    Ok(Card { lines: q.lines, artist: q.artist, title: q.track, art_url: q.album_art })
+}
+
+/// Renders the current line, or saved quote `quote` (1-based), to a PNG.
+pub fn run(preset: Preset, quote: Option<usize>, output: &str, offset_ms: i64) -> Result<(), String> {
+    let card = match quote {
+        Some(n) => saved_quote(n)?,
+        None => current(offset_ms)?,
+    };
+    render(&card, preset)
+        .save_with_format(output, ImageFormat::Png)
+        .map_err(|e| format!("failed to write {output}: {e}"))?;
+    eprintln!("wrote {output}");
+    Ok(())
+}
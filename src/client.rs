@@ -1,18 +1,15 @@
 use crate::daemon::SOCK_PATH;
 use crate::lyrics;
+use crate::player::{Device, QueueItem};
+use crate::sources::Status;
 use crate::spotify::{fmt_time, NowPlaying, State};
 use std::io::{BufRead, BufReader, Write};
 use std::os::unix::net::UnixStream;
 use std::process::Command;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-
-fn now_ms() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as u64
-}
+use std::time::Duration;
 
+// the daemon outlives this process, so the child is deliberately never waited on
+#[allow(clippy::zombie_processes)]
 pub fn spawn_daemon() {
     let exe = std::env::current_exe().unwrap();
     Command::new(exe)
@@ -38,17 +35,6 @@ fn connect() -> Option<UnixStream> {
     None
 }
 
-pub fn estimate(state: &State) -> Option<NowPlaying> {
-    let mut np = state.now_playing.clone()?;
-    if np.is_playing {
-        let elapsed = now_ms().saturating_sub(state.fetched_at_ms);
-        let estimated = (np.progress_ms + elapsed).min(np.duration_ms);
-        np.progress_ms = estimated;
-        np.progress = fmt_time(estimated);
-    }
-    Some(np)
-}
-
 pub fn fetch_state() -> State {
     let stream = match connect() {
         Some(s) => s,
@@ -68,19 +54,24 @@ pub fn fetch_state() -> State {
     serde_json::from_str(&line).unwrap()
 }
 
-fn current_lyric(state: &State, np: &NowPlaying, offset_ms: i64) -> Option<String> {
+/// Index of the line playing now, with the user's and the saved offsets applied.
+pub fn current_index(state: &State, np: &NowPlaying, offset_ms: i64) -> Option<usize> {
     let ly = state.lyrics.as_ref()?;
     if !ly.synced {
         return None;
     }
-    let adjusted = (np.progress_ms as i64 + offset_ms).max(0) as u64;
-    let idx = lyrics::current_line_index(&ly.lines, adjusted)?;
-    Some(ly.lines[idx].text.clone())
+    let adjusted = (np.progress_ms as i64 + offset_ms + state.offset_ms()).max(0) as u64;
+    lyrics::current_line_index(&ly.lines, adjusted)
+}
+
+fn current_lyric(state: &State, np: &NowPlaying, offset_ms: i64) -> Option<String> {
+    let idx = current_index(state, np, offset_ms)?;
+    Some(state.lyrics.as_ref()?.lines[idx].text.clone())
 }
 
 pub fn json(offset_ms: i64) {
     let state = fetch_state();
-    let Some(np) = estimate(&state) else {
+    let Some(np) = state.estimate() else {
         println!("null");
         return;
     };
@@ -89,10 +80,11 @@ pub fn json(offset_ms: i64) {
     let mut out = serde_json::to_value(&np).unwrap();
     out["lyric"] = serde_json::json!(lyric);
     out["lyrics"] = serde_json::json!(state.lyrics);
+    out["source"] = serde_json::json!(state.source);
     println!("{}", serde_json::to_string(&out).unwrap());
 }
 
-pub fn send_command(cmd_json: &str) -> Result<String, String> {
+fn request(cmd_json: &str) -> Result<serde_json::Value, String> {
     let mut stream = connect().ok_or("failed to connect to daemon")?;
     stream
         .write_all(cmd_json.as_bytes())
@@ -116,13 +108,135 @@ pub fn send_command(cmd_json: &str) -> Result<String, String> {
     if let Some(err) = v.get("error").and_then(|e| e.as_str()) {
         Err(err.to_string())
     } else {
-        Ok("ok".to_string())
+        Ok(v)
+    }
+}
+
+pub fn send_command(cmd_json: &str) -> Result<String, String> {
+    request(cmd_json).map(|_| "ok".to_string())
+}
+
+/// Runs a daemon command that answers with data, e.g. `search`.
+pub fn query(cmd: &str, arg: &str) -> Result<serde_json::Value, String> {
+    let req = serde_json::json!({"cmd": cmd, "arg": arg}).to_string();
+    Ok(request(&req)?["data"].take())
+}
+
+/// The current track's lyrics as fetched, before profanity masking.
+pub fn fetch_raw_lyrics() -> Result<Option<crate::lyrics::Lyrics>, String> {
+    let data = query("raw_lyrics", "")?;
+    serde_json::from_value(data).map_err(|e| format!("bad lyrics: {e}"))
+}
+
+/// Upcoming tracks from the daemon's player.
+pub fn fetch_queue() -> Result<Vec<QueueItem>, String> {
+    let data = query("queue", "")?;
+    serde_json::from_value(data).map_err(|e| format!("bad queue: {e}"))
+}
+
+/// `lirik queue`: what's up next, numbered for the TUI's and web UI's skip.
+pub fn print_queue() {
+    let queue = fetch_queue().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    if queue.is_empty() {
+        println!("queue is empty");
+    }
+    let width = queue.len().to_string().len();
+    for (i, item) in queue.iter().enumerate() {
+        let n = i + 1;
+        println!("{n:>width$}. {} - {}  {}", item.artist, item.track, fmt_time(item.duration_ms));
     }
 }
 
-pub fn plain(from_current: bool, reverse: bool, offset_ms: i64) {
+pub fn fetch_devices() -> Result<Vec<Device>, String> {
+    let data = query("devices", "")?;
+    serde_json::from_value(data).map_err(|e| format!("bad device list: {e}"))
+}
+
+/// `lirik devices`: numbered for `lirik device <n>`, `*` on the active one.
+pub fn print_devices() {
+    let devices = fetch_devices().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    if devices.is_empty() {
+        println!("no devices (open Spotify somewhere first)");
+    }
+    let name_w = devices.iter().map(|d| d.info.name.chars().count()).max().unwrap_or(0);
+    let type_w = devices.iter().map(|d| d.info.device_type.len()).max().unwrap_or(0);
+    for (i, d) in devices.iter().enumerate() {
+        let mark = if d.active { '*' } else { ' ' };
+        let volume = d.info.volume.map_or(String::new(), |v| format!("vol {v}%"));
+        let line = format!(
+            "{:>2}. {mark} {:name_w$}  {:type_w$}  {volume}",
+            i + 1,
+            d.info.name,
+            d.info.device_type
+        );
+        println!("{}", line.trim_end());
+    }
+}
+
+/// `lirik source list`: every player the daemon follows, `*` on the one shown.
+pub fn print_sources() {
+    let data = query("sources", "").unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    let list: Vec<Status> = serde_json::from_value(data).unwrap_or_default();
+    let width = list.iter().map(|s| s.name.len()).max().unwrap_or(0);
+    for s in list {
+        let mark = if s.shown { '*' } else { ' ' };
+        let status = match (&s.track, s.playing) {
+            (None, _) => "idle",
+            (Some(_), true) => "playing",
+            (Some(_), false) => "paused",
+        };
+        let mut line = format!("{mark} {:width$}  {status:7}", s.name);
+        if let Some(track) = &s.track {
+            line += &format!("  {track}");
+        }
+        if s.pinned {
+            line += "  (pinned)";
+        }
+        println!("{}", line.trim_end());
+    }
+}
+
+/// `lirik loop` without a range: the active loop, then the numbered lines
+/// to pick one from.
+pub fn print_loop() {
     let state = fetch_state();
-    let np = estimate(&state);
+    let Some(ly) = &state.lyrics else {
+        println!("no lyrics found");
+        return;
+    };
+    let looping = state.looping.as_ref();
+    match looping {
+        Some(l) => {
+            let times = l.remaining.map_or("until cleared".into(), |n| format!("{n} more time(s)"));
+            println!(
+                "looping lines {}-{} ({} - {}), {times}",
+                l.from + 1,
+                l.to + 1,
+                fmt_time(l.start_ms),
+                fmt_time(l.end_ms)
+            );
+        }
+        None => println!("no loop (lirik loop <from> <to> [--times <n>])"),
+    }
+    println!();
+    for (i, line) in ly.lines.iter().enumerate() {
+        let inside = looping.is_some_and(|l| (l.from..=l.to).contains(&i));
+        println!("{}{:>4}  {}", if inside { '>' } else { ' ' }, i + 1, line.text);
+    }
+}
+
+pub fn plain(from_current: bool, reverse: bool, compact: bool, offset_ms: i64) {
+    let state = fetch_state();
+    let np = state.estimate();
 
     let Some(ly) = &state.lyrics else {
         println!("no lyrics found");
@@ -134,20 +248,22 @@ pub fn plain(from_current: bool, reverse: bool, offset_ms: i64) {
             println!("nothing playing right now");
             return;
         };
-        let adjusted = (np.progress_ms as i64 + offset_ms).max(0) as u64;
+        let adjusted = (np.progress_ms as i64 + offset_ms + state.offset_ms()).max(0) as u64;
         lyrics::current_line_index(&ly.lines, adjusted).unwrap_or(0)
     } else {
         0
     };
 
     let lines = &ly.lines[start..];
-    if reverse {
-        for line in lines.iter().rev() {
-            println!("{}", line.text);
-        }
+    let mut out: Vec<String> = if compact {
+        lyrics::compact(lines)
     } else {
-        for line in lines {
-            println!("{}", line.text);
-        }
+        lines.iter().map(|l| l.text.clone()).collect()
+    };
+    if reverse {
+        out.reverse();
+    }
+    for line in out {
+        println!("{line}");
     }
 }
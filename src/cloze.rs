@@ -0,0 +1,160 @@
+use crate::find::overlap;
+use crate::lyrics::{self, LyricLine};
+use crate::search::normalize;
+
+pub const MAX_LEVEL: u32 = 5;
+
+/// Memorization mode: hides words in the lines still to come, more of them
+/// at each level, and scores lines typed from memory.
+pub struct Cloze {
+    pub level: u32,
+    /// Percent of words hidden per level.
+    step: u32,
+    // per track, so every song gets its own pattern of gaps
+    seed: u64,
+    pub typing: Option<Typing>,
+    /// Accuracy of every line typed so far, 0.0 to 1.0.
+    pub scores: Vec<f64>,
+}
+
+pub struct Typing {
+    /// Line being typed, before it's sung.
+    pub line: usize,
+    pub input: String,
+}
+
+/// Words of a line, each with its enhanced-LRC `<mm:ss.xx>` time when the
+/// line carries word timing.
+fn words(text: &str) -> Vec<(Option<u64>, String)> {
+    let mut out = Vec::new();
+    // a tag after a word times the next one
+    let mut pending = None;
+    for token in text.split_whitespace() {
+        let mut time = pending.take();
+        let mut word = String::new();
+        let mut rest = token;
+        let tag = |s: &str| s.find('<').and_then(|o| Some((o, o + s[o..].find('>')?)));
+        while let Some((open, close)) = tag(rest) {
+            word += &rest[..open];
+            match lyrics::parse_timestamp(&rest[open + 1..close]) {
+                Some(t) if word.is_empty() => time = Some(t),
+                Some(t) => pending = Some(t),
+                None => word += &rest[open..=close],
+            }
+            rest = &rest[close + 1..];
+        }
+        word += rest;
+        if word.is_empty() {
+            pending = pending.or(time);
+        } else {
+            out.push((time, word));
+        }
+    }
+    out
+}
+
+fn blank(word: &str) -> String {
+    word.chars().map(|c| if c.is_alphanumeric() { '_' } else { c }).collect()
+}
+
+// FNV-1a, so a song's gaps stay the same across Rust releases, which
+// DefaultHasher doesn't promise
+fn fnv1a(parts: &[&[u8]]) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in parts.iter().flat_map(|p| p.iter()) {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+    h
+}
+
+impl Cloze {
+    pub fn new(step: u32, track: &str) -> Self {
+        let mut c = Self {
+            level: 1,
+            step: step.clamp(1, 100),
+            seed: 0,
+            typing: None,
+            scores: Vec::new(),
+        };
+        c.retrack(track);
+        c
+    }
+
+    pub fn retrack(&mut self, track: &str) {
+        self.seed = fnv1a(&[track.as_bytes()]);
+        self.typing = None;
+    }
+
+    pub fn harder(&mut self) {
+        self.level = (self.level + 1).min(MAX_LEVEL);
+    }
+
+    pub fn easier(&mut self) {
+        self.level = self.level.saturating_sub(1).max(1);
+    }
+
+    pub fn hidden_pct(&self) -> u32 {
+        (self.level * self.step).min(100)
+    }
+
+    // a word stays hidden at every level above the one that first hid it
+    fn hides(&self, line: usize, word: usize) -> bool {
+        let (line, word) = ((line as u64).to_le_bytes(), (word as u64).to_le_bytes());
+        let h = fnv1a(&[&self.seed.to_le_bytes(), &line, &word]);
+        (h % 100) < self.hidden_pct() as u64
+    }
+
+    /// `text` as shown: past lines in full, the current line word by word
+    /// when it has word timing, later lines with gaps.
+    pub fn line(&self, idx: usize, text: &str, current: Option<usize>, progress_ms: u64) -> String {
+        let past = current.is_some_and(|c| idx < c);
+        let now = current == Some(idx);
+        words(text)
+            .iter()
+            .enumerate()
+            .map(|(i, (time, word))| {
+                let sung = past || (now && time.is_none_or(|t| t <= progress_ms));
+                if sung || !self.hides(idx, i) { word.clone() } else { blank(word) }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Starts typing the next line with words in it after `current`.
+    pub fn start_typing(&mut self, lines: &[LyricLine], current: Option<usize>) -> bool {
+        let from = current.map_or(0, |c| c + 1);
+        let next = (from..lines.len()).find(|&i| !normalize(&plain(&lines[i].text)).is_empty());
+        self.typing = next.map(|line| Typing { line, input: String::new() });
+        self.typing.is_some()
+    }
+
+    /// Scores what was typed against the real line; returns the accuracy and
+    /// the line.
+    pub fn submit(&mut self, lines: &[LyricLine]) -> Option<(f64, String)> {
+        let typing = self.typing.take()?;
+        let real = plain(&lines.get(typing.line)?.text);
+        let want_norm = normalize(&real);
+        let want: Vec<&str> = want_norm.split(' ').filter(|w| !w.is_empty()).collect();
+        if want.is_empty() {
+            return None;
+        }
+        let score = overlap(&want, &normalize(&typing.input));
+        self.scores.push(score);
+        Some((score, real))
+    }
+
+    pub fn status(&self) -> String {
+        let mut s = format!("cloze L{} ({}% hidden)", self.level, self.hidden_pct());
+        if !self.scores.is_empty() {
+            let avg = self.scores.iter().sum::<f64>() / self.scores.len() as f64;
+            s += &format!("  typed {} avg {:.0}%", self.scores.len(), avg * 100.0);
+        }
+        s
+    }
+}
+
+/// A line without its word timing tags.
+pub fn plain(text: &str) -> String {
+    words(text).into_iter().map(|(_, w)| w).collect::<Vec<_>>().join(" ")
+}
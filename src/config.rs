@@ -2,34 +2,140 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
+    pub player: Backend,
+    /// Players to follow at once, overriding `player` when set.
+    pub players: Vec<Backend>,
+    pub source_priority: Priority,
+    /// MPRIS player to follow, e.g. "spotify" or "mpv"; empty picks the one playing.
+    pub mpris_player: String,
+    /// MPD to follow: "host:port", or the path of its unix socket.
+    pub mpd_address: String,
+    pub mpd_password: String,
     pub client_id: String,
     pub client_secret: String,
     pub redirect_uri: String,
     pub poll_interval_secs: u64,
     pub lyrics_offset_ms: i64,
     pub web_port: u16,
+    pub mask_profanity: MaskMode,
+    pub profanity_languages: Vec<String>,
+    pub profanity_words: Vec<String>,
+    pub lyrics_dirs: Vec<String>,
+    /// Share of words the cloze mode hides per difficulty level, in percent.
+    pub cloze_step_percent: u32,
+}
+
+/// Which player the daemon follows.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Spotify,
+    Mpris,
+    Mpd,
+    /// Only what scripts push in with `lirik ingest`.
+    Ingest,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Spotify => "spotify",
+            Self::Mpris => "mpris",
+            Self::Mpd => "mpd",
+            Self::Ingest => "ingest",
+        }
+    }
+}
+
+/// Which source is shown when several players have something on.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// The one that most recently started playing.
+    Recent,
+    /// Only what's playing, first in `players` order.
+    Playing,
+    /// The first one in `players` order with a track.
+    Order,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MaskMode {
+    Off,
+    On,
+    Explicit,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            player: Backend::Spotify,
+            players: Vec::new(),
+            source_priority: Priority::Recent,
+            mpris_player: String::new(),
+            mpd_address: "localhost:6600".into(),
+            mpd_password: String::new(),
             client_id: String::new(),
             client_secret: String::new(),
             redirect_uri: "http://127.0.0.1:8888/callback".into(),
             poll_interval_secs: 5,
             lyrics_offset_ms: 0,
             web_port: 0,
+            mask_profanity: MaskMode::Off,
+            profanity_languages: vec!["en".into()],
+            profanity_words: Vec::new(),
+            lyrics_dirs: Vec::new(),
+            cloze_step_percent: 20,
         }
     }
 }
 
+impl Config {
+    /// `players`, or just `player` when that's empty.
+    pub fn backends(&self) -> Vec<Backend> {
+        let listed = match self.players.is_empty() {
+            true => std::slice::from_ref(&self.player),
+            false => &self.players,
+        };
+        let mut out: Vec<Backend> = Vec::new();
+        for b in listed {
+            if !out.contains(b) {
+                out.push(*b);
+            }
+        }
+        out
+    }
+}
+
 pub fn path() -> PathBuf {
     dirs::config_dir()
         .unwrap_or_else(|| PathBuf::from("~/.config"))
         .join("lirik/config.toml")
 }
 
+pub fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("~/.cache"))
+        .join("lirik")
+}
+
+pub fn data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("~/.local/share"))
+        .join("lirik")
+}
+
+/// Expands a leading `~/` to the home directory.
+pub fn expand(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
 pub fn load() -> Option<Config> {
     let contents = std::fs::read_to_string(path()).ok()?;
     toml::from_str(&contents).ok()
@@ -1,31 +1,35 @@
-use crate::{lyrics, spotify, web};
-use rspotify::model::RepeatState;
-use rspotify::prelude::*;
-use rspotify::AuthCodeSpotify;
+use crate::player::{Control, Device, PlayerBackend, Repeat};
+use crate::config::Priority;
+use crate::ingest::{self, Ingest};
+use crate::sources::{Source, Sources};
+use crate::{lyrics, offsets, profanity, search, spotify, web};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::UnixListener;
 use tokio::sync::{Notify, RwLock};
 
 pub const SOCK_PATH: &str = "/tmp/lirik.sock";
 pub const PID_PATH: &str = "/tmp/lirik.pid";
+const SEARCH_LIMIT: usize = 50;
+const LOOP_TICK_MS: u64 = 100;
 
 pub fn kill() {
-    if let Ok(raw) = std::fs::read_to_string(PID_PATH) {
-        if let Ok(pid) = raw.trim().parse::<i32>() {
-            unsafe { libc::kill(pid, libc::SIGTERM); }
-            for _ in 0..20 {
-                std::thread::sleep(Duration::from_millis(50));
-                if unsafe { libc::kill(pid, 0) } != 0 {
-                    break;
-                }
-            }
-            if unsafe { libc::kill(pid, 0) } == 0 {
-                unsafe { libc::kill(pid, libc::SIGKILL); }
-                std::thread::sleep(Duration::from_millis(100));
+    if let Ok(raw) = std::fs::read_to_string(PID_PATH)
+        && let Ok(pid) = raw.trim().parse::<i32>()
+    {
+        unsafe { libc::kill(pid, libc::SIGTERM); }
+        for _ in 0..20 {
+            std::thread::sleep(Duration::from_millis(50));
+            if unsafe { libc::kill(pid, 0) } != 0 {
+                break;
             }
         }
+        if unsafe { libc::kill(pid, 0) } == 0 {
+            unsafe { libc::kill(pid, libc::SIGKILL); }
+            std::thread::sleep(Duration::from_millis(100));
+        }
     }
     let _ = std::fs::remove_file(SOCK_PATH);
     let _ = std::fs::remove_file(PID_PATH);
@@ -38,18 +42,45 @@ fn now_ms() -> u64 {
         .as_millis() as u64
 }
 
+/// A device by ID, by its 1-based number in `lirik devices`, or by name:
+/// exact first, then a unique partial match, ignoring case.
+fn pick_device<'a>(devices: &'a [Device], arg: &str) -> Result<&'a Device, String> {
+    if let Some(d) = devices.iter().find(|d| d.info.id.as_deref() == Some(arg)) {
+        return Ok(d);
+    }
+    if let Ok(n) = arg.parse::<usize>() {
+        return n
+            .checked_sub(1)
+            .and_then(|i| devices.get(i))
+            .ok_or_else(|| format!("no device #{n} ({} available)", devices.len()));
+    }
+    let want = arg.to_lowercase();
+    if let Some(d) = devices.iter().find(|d| d.info.name.to_lowercase() == want) {
+        return Ok(d);
+    }
+    let partial: Vec<&Device> = devices
+        .iter()
+        .filter(|d| d.info.name.to_lowercase().contains(&want))
+        .collect();
+    match partial[..] {
+        [d] => Ok(d),
+        [] => Err(format!("no device named {arg}")),
+        _ => {
+            let names: Vec<&str> = partial.iter().map(|d| d.info.name.as_str()).collect();
+            Err(format!("{arg} could be {}", names.join(" or ")))
+        }
+    }
+}
+
 pub async fn execute_cmd(
-    client: &AuthCodeSpotify,
+    player: &dyn PlayerBackend,
     state: &RwLock<spotify::State>,
     cmd: &str,
     arg: Option<&str>,
 ) -> Result<(), String> {
-    match cmd {
-        "pause" => client.pause_playback(None).await.map_err(|e| e.to_string()),
-        "play" => client
-            .resume_playback(None, None)
-            .await
-            .map_err(|e| e.to_string()),
+    let control = match cmd {
+        "pause" => Control::Pause,
+        "play" => Control::Play,
         "toggle" => {
             let playing = state
                 .read()
@@ -58,34 +89,21 @@ pub async fn execute_cmd(
                 .as_ref()
                 .map(|n| n.is_playing)
                 .unwrap_or(false);
-            if playing {
-                client.pause_playback(None).await
-            } else {
-                client.resume_playback(None, None).await
-            }
-            .map_err(|e| e.to_string())
-        }
-        "next" => client.next_track(None).await.map_err(|e| e.to_string()),
-        "prev" => client
-            .previous_track(None)
-            .await
-            .map_err(|e| e.to_string()),
-        "volume" => {
-            let val: u8 = arg
-                .ok_or("missing volume value")?
-                .parse()
-                .map_err(|_| "invalid volume (0-100)")?;
-            client.volume(val, None).await.map_err(|e| e.to_string())
+            if playing { Control::Pause } else { Control::Play }
         }
+        "next" => Control::Next,
+        "prev" => Control::Prev,
+        "volume" => Control::Volume(
+            arg.ok_or("missing volume value")?
+                .parse()
+                .map_err(|_| "invalid volume (0-100)")?,
+        ),
         "seek" => {
             let ms: i64 = arg
                 .ok_or("missing seek position")?
                 .parse()
                 .map_err(|_| "invalid seek value")?;
-            client
-                .seek_track(chrono::Duration::milliseconds(ms), None)
-                .await
-                .map_err(|e| e.to_string())
+            Control::Seek(ms.max(0) as u64)
         }
         "shuffle" => {
             let current = state
@@ -95,10 +113,7 @@ pub async fn execute_cmd(
                 .as_ref()
                 .map(|n| n.shuffle)
                 .unwrap_or(false);
-            client
-                .shuffle(!current, None)
-                .await
-                .map_err(|e| e.to_string())
+            Control::Shuffle(!current)
         }
         "repeat" => {
             let current = state
@@ -108,63 +123,251 @@ pub async fn execute_cmd(
                 .as_ref()
                 .map(|n| n.repeat.as_str().to_string())
                 .unwrap_or_default();
-            let next = match current.as_str() {
-                "off" => RepeatState::Context,
-                "context" => RepeatState::Track,
-                _ => RepeatState::Off,
+            Control::Repeat(match current.as_str() {
+                "off" => Repeat::Context,
+                "context" => Repeat::Track,
+                _ => Repeat::Off,
+            })
+        }
+        // saves the offset for the current track and lyrics source
+        "offset" => {
+            let ms: i64 = arg
+                .ok_or("missing offset")?
+                .parse()
+                .map_err(|_| "invalid offset (ms)")?;
+            let mut s = state.write().await;
+            let id = s
+                .now_playing
+                .as_ref()
+                .and_then(|n| n.id.clone())
+                .ok_or("nothing playing right now")?;
+            let source = s.lyrics.as_ref().ok_or("no lyrics found")?.source.clone();
+            let mut store = offsets::load();
+            store.set_track(&id, &source, ms);
+            store.save()?;
+            s.track_offset_ms = ms;
+            return Ok(());
+        }
+        // saves a latency offset; arg is "<ms> [device name]", the playing
+        // device when no name is given
+        "device_offset" => {
+            let arg = arg.ok_or("missing offset")?;
+            let (ms, name) = match arg.split_once(' ') {
+                Some((ms, name)) => (ms, Some(name)),
+                None => (arg, None),
+            };
+            let ms: i64 = ms.parse().map_err(|_| "invalid offset (ms)")?;
+            let mut s = state.write().await;
+            let playing = s
+                .now_playing
+                .as_ref()
+                .and_then(|n| n.device.as_ref())
+                .map(|d| d.name.clone());
+            let device = name.map(str::to_string).or(playing.clone()).ok_or("no active device")?;
+            let mut store = offsets::load();
+            store.set_device(&device, ms);
+            store.save()?;
+            if playing.as_deref() == Some(device.as_str()) {
+                s.device_offset_ms = ms;
+            }
+            return Ok(());
+        }
+        // arg is "<track uri or id> [position ms]"
+        "play_uri" => {
+            let arg = arg.ok_or("missing track uri")?;
+            let (uri, pos) = match arg.split_once(' ') {
+                Some((uri, pos)) => (uri, Some(pos.parse().map_err(|_| "invalid position")?)),
+                None => (arg, None),
             };
-            client.repeat(next, None).await.map_err(|e| e.to_string())
+            Control::PlayUri { uri: uri.to_string(), position_ms: pos }
         }
-        other => Err(format!("unknown command: {other}")),
-    }
+        // arg is a device ID or name, or its number in `lirik devices`
+        "device" => {
+            let devices = player.devices().await?;
+            let device = pick_device(&devices, arg.ok_or("missing device")?)?;
+            let id = device
+                .info
+                .id
+                .as_deref()
+                .ok_or_else(|| format!("{} can't take playback", device.info.name))?;
+            return player.transfer(id).await;
+        }
+        // arg is a 1-based position in the queue; goes to the player the
+        // queue came from, not necessarily the one shown
+        "queue_skip" => {
+            let n: usize = arg
+                .ok_or("missing queue position")?
+                .parse()
+                .map_err(|_| "invalid queue position")?;
+            if n == 0 {
+                return Err("queue positions start at 1".into());
+            }
+            return player.skip_queued(n).await;
+        }
+        // arg is "<from line> <to line> [times]", 1-based, or "off"
+        "loop" => {
+            let arg = arg.ok_or("missing loop range")?;
+            let mut s = state.write().await;
+            if arg == "off" {
+                s.looping = None;
+                return Ok(());
+            }
+            let mut nums = arg
+                .split_whitespace()
+                .map(|n| n.parse::<usize>().map_err(|_| format!("invalid number: {n}")));
+            let from = nums.next().ok_or("missing start line")??;
+            let to = nums.next().ok_or("missing end line")??;
+            let times = nums.next().transpose()?;
+            if times == Some(0) {
+                return Err("times must be at least 1".into());
+            }
+            let duration_ms = s.now_playing.as_ref().ok_or("nothing playing right now")?.duration_ms;
+            let ly = s.lyrics.as_ref().ok_or("no lyrics found")?;
+            if !ly.synced {
+                return Err("lyrics aren't synced, there is nothing to loop on".into());
+            }
+            if from == 0 || from > to || to > ly.lines.len() {
+                return Err(format!("lines must be in 1-{}, start before end", ly.lines.len()));
+            }
+            let (from, to) = (from - 1, to - 1);
+            s.looping = Some(spotify::Loop {
+                from,
+                to,
+                start_ms: ly.lines[from].time_ms,
+                // the end line is over once the next one starts
+                end_ms: ly.lines.get(to + 1).map_or(duration_ms, |l| l.time_ms),
+                remaining: times.map(|n| n as u32),
+            });
+            return Ok(());
+        }
+        other => return Err(format!("unknown command: {other}")),
+    };
+    player.control(control).await
 }
 
-pub async fn run(client: AuthCodeSpotify, poll_secs: u64, web_port: u16) {
+pub async fn run(
+    players: Vec<Source>,
+    priority: Priority,
+    poll_secs: u64,
+    lyrics_offset_ms: i64,
+    web_port: u16,
+    filter: profanity::Filter,
+) {
     std::fs::write(PID_PATH, std::process::id().to_string()).ok();
     let _ = std::fs::remove_file(SOCK_PATH);
 
+    // pushed-in state (`lirik ingest`) goes ahead of the configured players
+    let ingest = Arc::new(Ingest::default());
+    let mut all = vec![Source { name: "ingest".into(), player: ingest.clone() }];
+    all.extend(players);
+    let sources = Arc::new(Sources::new(all, priority));
+    let player: Arc<dyn PlayerBackend> = sources.clone();
+
     let listener = UnixListener::bind(SOCK_PATH).expect("failed to bind unix socket");
     let state: Arc<RwLock<spotify::State>> = Arc::new(RwLock::new(spotify::State {
         now_playing: None,
         fetched_at_ms: now_ms(),
         lyrics: None,
+        track_offset_ms: 0,
+        device_offset_ms: 0,
+        looping: None,
+        source: None,
     }));
-    let client = Arc::new(client);
+    // the lyrics as fetched, before masking, for the sync editor to save and publish
+    let raw_lyrics: Arc<RwLock<Option<lyrics::Lyrics>>> = Arc::new(RwLock::new(None));
     let repoll = Arc::new(Notify::new());
+    let reload = Arc::new(AtomicBool::new(false));
+    let index: Arc<RwLock<Option<search::Index>>> = Arc::new(RwLock::new(None));
+    let filter = Arc::new(filter);
+    player.subscribe(repoll.clone());
 
     // web server
     if web_port > 0 {
-        tokio::spawn(web::serve(web_port, state.clone(), client.clone()));
+        tokio::spawn(web::serve(web_port, state.clone(), player.clone()));
     }
 
     // poll loop
     let poll_state = state.clone();
-    let poll_client = client.clone();
+    let poll_sources = sources.clone();
     let poll_notify = repoll.clone();
+    let poll_reload = reload.clone();
+    let poll_index = index.clone();
+    let poll_raw = raw_lyrics.clone();
+    let poll_filter = filter.clone();
     let poll_interval = Duration::from_secs(poll_secs);
     let poll_handle = tokio::spawn(async move {
         let mut current_track = String::new();
+        let mut current_device = String::new();
         loop {
-            let np = spotify::now_playing(&poll_client).await;
+            let (source, np) = match poll_sources.poll().await {
+                Some((source, np)) => (Some(source), Some(np)),
+                None => (None, None),
+            };
 
             let track_key = np
                 .as_ref()
                 .map(|n| format!("{}\0{}", n.artist, n.track))
                 .unwrap_or_default();
 
-            let ly = if track_key != current_track {
+            let changed = track_key != current_track;
+            let refetch = changed || poll_reload.swap(false, Ordering::Relaxed);
+            let ly = if refetch {
                 current_track = track_key;
                 match &np {
-                    Some(n) => lyrics::fetch(&n.artist, &n.track, n.duration_ms).await,
-                    None => None,
+                    Some(n) => {
+                        let query = lyrics::Query {
+                            artist: n.artist.clone(),
+                            title: n.track.clone(),
+                            album: n.album.clone(),
+                            duration_ms: n.duration_ms,
+                        };
+                        let mut ly = lyrics::fetch(&query).await;
+                        *poll_raw.write().await = ly.clone();
+                        if let Some(ly) = ly.as_mut()
+                            && poll_filter.applies(n.explicit)
+                        {
+                            poll_filter.mask(ly);
+                        }
+                        ly
+                    }
+                    None => {
+                        *poll_raw.write().await = None;
+                        None
+                    }
                 }
             } else {
                 poll_state.read().await.lyrics.clone()
             };
 
+            // a new track may have just landed in the cache
+            if refetch {
+                let index = poll_index.clone();
+                tokio::spawn(async move { search::refresh(&index).await });
+            }
+
             {
                 let mut s = poll_state.write().await;
+                if changed {
+                    s.looping = None;
+                }
+                if refetch {
+                    let id = np.as_ref().and_then(|n| n.id.as_deref());
+                    s.track_offset_ms = match (id, &ly) {
+                        (Some(id), Some(ly)) => offsets::load().track(id, &ly.source),
+                        _ => 0,
+                    };
+                }
+                let device = np
+                    .as_ref()
+                    .and_then(|n| n.device.as_ref())
+                    .map(|d| d.name.clone())
+                    .unwrap_or_default();
+                if device != current_device {
+                    s.device_offset_ms = offsets::load().device(&device);
+                    current_device = device;
+                }
                 s.now_playing = np;
+                s.source = source;
                 s.fetched_at_ms = now_ms();
                 s.lyrics = ly;
             }
@@ -176,6 +379,56 @@ pub async fn run(client: AuthCodeSpotify, poll_secs: u64, web_port: u16) {
         }
     });
 
+    // A-B loop: checked between polls from the estimated position, so the
+    // jump back lands close to the end of the last line. Clients add their own
+    // offset on top of the saved ones; the daemon only knows the configured one.
+    let loop_state = state.clone();
+    let loop_player = player.clone();
+    let loop_notify = repoll.clone();
+    tokio::spawn(async move {
+        let mut last = 0;
+        let mut quiet_until = Instant::now();
+        loop {
+            tokio::time::sleep(Duration::from_millis(LOOP_TICK_MS)).await;
+            let (pos, offset, looping) = {
+                let s = loop_state.read().await;
+                let pos = s.estimate().map_or(0, |np| np.progress_ms);
+                (pos, s.offset_ms() + lyrics_offset_ms, s.looping.clone())
+            };
+            let prev = std::mem::replace(&mut last, pos);
+            // Spotify may still report the old position right after a seek
+            let Some(lp) = looping.filter(|_| Instant::now() >= quiet_until) else { continue };
+            let end = (lp.end_ms as i64 - offset).max(0) as u64;
+            // a crossing, not a manual seek far past the end
+            if !(prev < end && pos >= end && pos < end + 2000) {
+                continue;
+            }
+            let start = (lp.start_ms as i64 - offset).max(0) as u64;
+            if loop_player.control(Control::Seek(start)).await.is_err() {
+                continue;
+            }
+            {
+                let mut s = loop_state.write().await;
+                if let Some(np) = s.now_playing.as_mut() {
+                    np.progress_ms = start;
+                }
+                s.fetched_at_ms = now_ms();
+                match lp.remaining {
+                    Some(1) => s.looping = None,
+                    Some(n) => {
+                        if let Some(l) = s.looping.as_mut() {
+                            l.remaining = Some(n - 1);
+                        }
+                    }
+                    None => {}
+                }
+            }
+            last = start;
+            quiet_until = Instant::now() + Duration::from_millis(1500);
+            loop_notify.notify_one();
+        }
+    });
+
     // unix socket
     let accept_handle = tokio::spawn(async move {
         loop {
@@ -183,8 +436,14 @@ pub async fn run(client: AuthCodeSpotify, poll_secs: u64, web_port: u16) {
                 continue;
             };
             let state = state.clone();
-            let client = client.clone();
+            let player = player.clone();
             let repoll = repoll.clone();
+            let reload = reload.clone();
+            let index = index.clone();
+            let ingest = ingest.clone();
+            let sources = sources.clone();
+            let raw_lyrics = raw_lyrics.clone();
+            let filter = filter.clone();
 
             tokio::spawn(async move {
                 let (reader, mut writer) = tokio::io::split(stream);
@@ -212,8 +471,61 @@ pub async fn run(client: AuthCodeSpotify, poll_secs: u64, web_port: u16) {
                         Ok(v) => {
                             let cmd = v["cmd"].as_str().unwrap_or("");
                             let arg = v["arg"].as_str();
-                            match execute_cmd(&client, &state, cmd, arg).await {
-                                Ok(()) => r#"{"ok":true}"#.to_string(),
+                            let result = match cmd {
+                                // refetch lyrics for the current track, e.g. after saving an override
+                                "reload" => {
+                                    reload.store(true, Ordering::Relaxed);
+                                    Ok(None)
+                                }
+                                // now playing from a script, for players lirik can't follow
+                                "set_now_playing" => serde_json::from_value::<ingest::Update>(v.clone())
+                                    .map_err(|e| format!("bad update: {e}"))
+                                    .and_then(|u| ingest.set(u))
+                                    .map(|()| None),
+                                "clear_now_playing" => {
+                                    ingest.clear();
+                                    Ok(None)
+                                }
+                                // unmasked, so edits never save or publish masked words
+                                "raw_lyrics" => Ok(Some(serde_json::json!(*raw_lyrics.read().await))),
+                                "sources" => Ok(Some(serde_json::json!(sources.list()))),
+                                // arg is a source name, or "auto" to unpin
+                                "source" => arg
+                                    .ok_or_else(|| "missing source name".to_string())
+                                    .and_then(|name| sources.pin(name))
+                                    .map(|()| None),
+                                "devices" => player.devices().await.map(|d| Some(serde_json::json!(d))),
+                                "queue" => player.queue().await.map(|q| Some(serde_json::json!(q))),
+                                // arg is a track URI or a search
+                                "queue_add" => match arg {
+                                    Some(query) => player
+                                        .enqueue(query)
+                                        .await
+                                        .map(|item| Some(serde_json::json!(item))),
+                                    None => Err("missing track".into()),
+                                },
+                                "search" => {
+                                    search::refresh(&index).await;
+                                    let mut hits = index
+                                        .read()
+                                        .await
+                                        .as_ref()
+                                        .map(|i| i.search(arg.unwrap_or(""), SEARCH_LIMIT))
+                                        .unwrap_or_default();
+                                    // the index doesn't know which songs are explicit, so
+                                    // "explicit" masks every hit
+                                    if filter.applies(true) {
+                                        for hit in &mut hits {
+                                            hit.text = filter.mask_line(&hit.text);
+                                        }
+                                    }
+                                    Ok(Some(serde_json::json!(hits)))
+                                }
+                                _ => execute_cmd(&*player, &state, cmd, arg).await.map(|()| None),
+                            };
+                            match result {
+                                Ok(None) => r#"{"ok":true}"#.to_string(),
+                                Ok(Some(data)) => serde_json::json!({"ok": true, "data": data}).to_string(),
                                 Err(e) => {
                                     let e = e.replace('"', r#"\""#);
                                     format!(r#"{{"error":"{e}"}}"#)
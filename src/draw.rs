@@ -0,0 +1,206 @@
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use image::imageops::FilterType;
+use image::{Rgba, RgbaImage};
+use std::collections::HashMap;
+
+// DejaVu Sans, see assets/fonts/LICENSE
+const REGULAR: &[u8] = include_bytes!("../assets/fonts/DejaVuSans.ttf");
+const BOLD: &[u8] = include_bytes!("../assets/fonts/DejaVuSans-Bold.ttf");
+
+pub struct Fonts {
+    pub regular: FontRef<'static>,
+    pub bold: FontRef<'static>,
+}
+
+impl Fonts {
+    pub fn load() -> Self {
+        Self {
+            regular: FontRef::try_from_slice(REGULAR).expect("bundled font"),
+            bold: FontRef::try_from_slice(BOLD).expect("bundled font"),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Palette {
+    pub top: Rgba<u8>,
+    pub bottom: Rgba<u8>,
+    pub text: Rgba<u8>,
+    pub dim: Rgba<u8>,
+}
+
+impl Default for Palette {
+    // the TUI's colours: near-black with white text
+    fn default() -> Self {
+        Self {
+            top: Rgba([32, 32, 32, 255]),
+            bottom: Rgba([12, 12, 12, 255]),
+            text: Rgba([255, 255, 255, 255]),
+            dim: Rgba([170, 170, 170, 255]),
+        }
+    }
+}
+
+fn scale(c: Rgba<u8>, f: f32) -> Rgba<u8> {
+    let [r, g, b, a] = c.0;
+    let s = |v: u8| (v as f32 * f).clamp(0.0, 255.0) as u8;
+    Rgba([s(r), s(g), s(b), a])
+}
+
+fn luminance(c: Rgba<u8>) -> f32 {
+    let [r, g, b, _] = c.0;
+    (0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32) / 255.0
+}
+
+impl Palette {
+    /// Background from the artwork's most common colour, with text that
+    /// stays readable on it.
+    pub fn from_art(art: &RgbaImage) -> Self {
+        let small = image::imageops::resize(art, 48, 48, FilterType::Triangle);
+        let mut buckets: HashMap<[u8; 3], (u32, [u32; 3])> = HashMap::new();
+        for p in small.pixels() {
+            let [r, g, b, _] = p.0;
+            let entry = buckets.entry([r >> 5, g >> 5, b >> 5]).or_default();
+            entry.0 += 1;
+            entry.1[0] += r as u32;
+            entry.1[1] += g as u32;
+            entry.1[2] += b as u32;
+        }
+        let Some((count, sum)) = buckets.into_values().max_by_key(|(n, _)| *n) else {
+            return Self::default();
+        };
+        let avg = |i: usize| (sum[i] / count) as u8;
+        let base = Rgba([avg(0), avg(1), avg(2), 255]);
+        // keep the gradient dark enough that white text works on most covers
+        let top = if luminance(base) > 0.6 { scale(base, 0.6) } else { base };
+        let bottom = scale(top, 0.45);
+        if luminance(top) > 0.55 {
+            Self { top, bottom, text: Rgba([20, 20, 20, 255]), dim: Rgba([60, 60, 60, 255]) }
+        } else {
+            Self { top, bottom, ..Self::default() }
+        }
+    }
+}
+
+pub fn fill_gradient(img: &mut RgbaImage, top: Rgba<u8>, bottom: Rgba<u8>) {
+    let h = img.height().max(2) as f32 - 1.0;
+    for (_, y, p) in img.enumerate_pixels_mut() {
+        let t = y as f32 / h;
+        let mix = |i: usize| (top.0[i] as f32 * (1.0 - t) + bottom.0[i] as f32 * t) as u8;
+        *p = Rgba([mix(0), mix(1), mix(2), 255]);
+    }
+}
+
+/// Downloads and decodes album art.
+pub fn load_art(url: &str) -> Option<RgbaImage> {
+    let bytes = reqwest::blocking::get(url).and_then(|r| r.bytes()).ok()?;
+    Some(image::load_from_memory(&bytes).ok()?.to_rgba8())
+}
+
+pub fn paste_art(img: &mut RgbaImage, art: &RgbaImage, x: u32, y: u32, size: u32) {
+    let art = image::imageops::resize(art, size, size, FilterType::Lanczos3);
+    image::imageops::overlay(img, &art, x as i64, y as i64);
+}
+
+pub fn text_width(font: &FontRef, px: f32, text: &str) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(px));
+    let mut width = 0.0;
+    let mut last = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(prev) = last {
+            width += scaled.kern(prev, id);
+        }
+        width += scaled.h_advance(id);
+        last = Some(id);
+    }
+    width
+}
+
+pub fn line_height(font: &FontRef, px: f32) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(px));
+    scaled.height() + scaled.line_gap()
+}
+
+/// Greedy word wrap to `max_width` pixels; overlong words get their own line.
+pub fn wrap(font: &FontRef, px: f32, text: &str, max_width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if current.is_empty() { word.to_string() } else { format!("{current} {word}") };
+        if !current.is_empty() && text_width(font, px, &candidate) > max_width {
+            lines.push(std::mem::replace(&mut current, word.to_string()));
+        } else {
+            current = candidate;
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// Draws one line of text with its top-left corner at (x, y).
+pub fn text(img: &mut RgbaImage, font: &FontRef, px: f32, x: f32, y: f32, s: &str, color: Rgba<u8>) {
+    let scale = PxScale::from(px);
+    let scaled = font.as_scaled(scale);
+    let baseline = y + scaled.ascent();
+    let mut caret = x;
+    let mut last = None;
+    for c in s.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(prev) = last {
+            caret += scaled.kern(prev, id);
+        }
+        let glyph = id.with_scale_and_position(scale, point(caret, baseline));
+        caret += scaled.h_advance(id);
+        last = Some(id);
+        let Some(outlined) = font.outline_glyph(glyph) else { continue };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if px < 0 || py < 0 || px >= img.width() as i32 || py >= img.height() as i32 {
+                return;
+            }
+            let a = coverage.min(1.0) * color.0[3] as f32 / 255.0;
+            let dst = img.get_pixel_mut(px as u32, py as u32);
+            for i in 0..3 {
+                dst.0[i] = (dst.0[i] as f32 * (1.0 - a) + color.0[i] as f32 * a) as u8;
+            }
+        });
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+}
+
+/// Draws pre-wrapped lines starting at `y`, aligned inside `x..x + width`.
+/// Returns the y just below the last line.
+#[allow(clippy::too_many_arguments)]
+pub fn lines(
+    img: &mut RgbaImage,
+    font: &FontRef,
+    px: f32,
+    x: f32,
+    y: f32,
+    width: f32,
+    align: Align,
+    rows: &[String],
+    color: Rgba<u8>,
+) -> f32 {
+    let step = line_height(font, px);
+    let mut y = y;
+    for row in rows {
+        let left = match align {
+            Align::Left => x,
+            Align::Center => x + (width - text_width(font, px, row)) / 2.0,
+        };
+        text(img, font, px, left, y, row, color);
+        y += step;
+    }
+    y
+}
@@ -0,0 +1,224 @@
+use crate::export::{self, Format};
+use crate::lyrics::{self, lrc_timestamp, LyricLine, Lyrics, Query};
+use ratatui::{
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{List, ListItem, ListState, Paragraph},
+    Frame,
+};
+use std::sync::{Arc, Mutex};
+
+const NUDGE_MS: i64 = 100;
+// replay starts a little before the line so you hear it come in
+const REPLAY_LEAD_MS: u64 = 2000;
+
+const HELP: &str = "space stamp  u undo  [ ] nudge  \u{2191}\u{2193} move  r replay  t play/pause  s save  P publish  esc close";
+
+/// Tap-to-sync state: one optional timestamp per non-empty lyric line.
+pub struct Editor {
+    query: Query,
+    lines: Vec<String>,
+    stamps: Vec<Option<u64>>,
+    cursor: usize,
+    // (line, previous stamp, previous cursor)
+    history: Vec<(usize, Option<u64>, usize)>,
+    status: Arc<Mutex<String>>,
+    // `P` was pressed once; a second `P` publishes
+    confirming: bool,
+}
+
+impl Editor {
+    pub fn new(query: Query, ly: &Lyrics) -> Self {
+        let kept: Vec<&LyricLine> = ly.lines.iter().filter(|l| !l.text.trim().is_empty()).collect();
+        Self {
+            query,
+            lines: kept.iter().map(|l| l.text.clone()).collect(),
+            stamps: kept.iter().map(|l| ly.synced.then_some(l.time_ms)).collect(),
+            cursor: 0,
+            history: Vec::new(),
+            status: Arc::new(Mutex::new(String::new())),
+            confirming: false,
+        }
+    }
+
+    fn set_status(&self, msg: impl Into<String>) {
+        *self.status.lock().unwrap() = msg.into();
+    }
+
+    pub fn stamp(&mut self, ms: u64) {
+        if self.cursor >= self.lines.len() {
+            return;
+        }
+        self.history.push((self.cursor, self.stamps[self.cursor], self.cursor));
+        self.stamps[self.cursor] = Some(ms);
+        self.cursor += 1;
+    }
+
+    pub fn undo(&mut self) {
+        if let Some((line, prev, cursor)) = self.history.pop() {
+            self.stamps[line] = prev;
+            self.cursor = cursor;
+        }
+    }
+
+    pub fn up(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.lines.len());
+    }
+
+    // the highlighted line if stamped, otherwise the one just stamped above it
+    fn target(&self) -> Option<usize> {
+        if self.stamps.get(self.cursor).copied().flatten().is_some() {
+            return Some(self.cursor);
+        }
+        let prev = self.cursor.checked_sub(1)?;
+        self.stamps[prev].is_some().then_some(prev)
+    }
+
+    pub fn nudge(&mut self, forward: bool) {
+        let Some(i) = self.target() else { return };
+        let Some(ms) = self.stamps[i] else { return };
+        let delta = if forward { NUDGE_MS } else { -NUDGE_MS };
+        self.history.push((i, Some(ms), self.cursor));
+        self.stamps[i] = Some((ms as i64 + delta).max(0) as u64);
+    }
+
+    pub fn replay_pos(&self) -> Option<u64> {
+        let ms = self.stamps[self.target()?]?;
+        Some(ms.saturating_sub(REPLAY_LEAD_MS))
+    }
+
+    fn to_lyrics(&self) -> Option<Lyrics> {
+        let mut lines: Vec<LyricLine> = self
+            .lines
+            .iter()
+            .zip(&self.stamps)
+            .filter_map(|(text, stamp)| Some(LyricLine { time_ms: (*stamp)?, text: text.clone() }))
+            .collect();
+        if lines.is_empty() {
+            return None;
+        }
+        lines.sort_by_key(|l| l.time_ms);
+        Some(Lyrics { synced: true, lines, source: "override".into() })
+    }
+
+    /// Writes the stamped lines as this track's LRC override.
+    pub fn save(&self) -> Result<(), String> {
+        let ly = self.to_lyrics().ok_or("nothing stamped yet")?;
+        let path = lyrics::override_path(&self.query);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let lrc = export::render(Format::Lrc, &self.query, &ly, 0)?;
+        std::fs::write(&path, lrc).map_err(|e| e.to_string())?;
+        self.set_status(format!(
+            "saved {}/{} lines to {}",
+            ly.lines.len(),
+            self.lines.len(),
+            path.display()
+        ));
+        Ok(())
+    }
+
+    /// Publishes to LRCLIB on a background thread; progress shows in the status line.
+    /// LRCLIB is public and uploads can't be taken back, so the first call only asks.
+    pub fn publish(&mut self) {
+        let Some(ly) = self.to_lyrics() else {
+            self.set_status("nothing stamped yet");
+            return;
+        };
+        if !std::mem::replace(&mut self.confirming, true) {
+            self.set_status("P again to publish to lrclib, a public database; this can't be undone");
+            return;
+        }
+        self.confirming = false;
+        let query = Query {
+            artist: self.query.artist.clone(),
+            title: self.query.title.clone(),
+            album: self.query.album.clone(),
+            duration_ms: self.query.duration_ms,
+        };
+        let status = self.status.clone();
+        self.set_status("publishing to lrclib (solving challenge)...");
+        std::thread::spawn(move || {
+            let result = tokio::runtime::Runtime::new()
+                .map_err(|e| e.to_string())
+                .and_then(|rt| rt.block_on(lyrics::publish(&query, &ly)));
+            *status.lock().unwrap() = match result {
+                Ok(()) => "published to lrclib".into(),
+                Err(e) => e,
+            };
+        });
+    }
+
+    /// Any key but `P` after the first `P` calls the upload off.
+    pub fn cancel_publish(&mut self) {
+        if std::mem::take(&mut self.confirming) {
+            self.set_status("not published");
+        }
+    }
+
+    pub fn fail(&self, e: String) {
+        self.set_status(e);
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect, accent: Color, dim: Color) {
+        let [list_area, status_area, help_area] = Layout::vertical([
+            Constraint::Min(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+
+        let items: Vec<ListItem> = self
+            .lines
+            .iter()
+            .zip(&self.stamps)
+            .enumerate()
+            .map(|(i, (text, stamp))| {
+                let ts = match stamp {
+                    Some(ms) => lrc_timestamp(*ms),
+                    None => "[--:--.--]".into(),
+                };
+                let style = if i == self.cursor {
+                    Style::default().fg(accent).add_modifier(Modifier::BOLD)
+                } else if stamp.is_some() {
+                    Style::default().fg(Color::Gray)
+                } else {
+                    Style::default().fg(dim)
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{ts} "), Style::default().fg(dim)),
+                    Span::styled(text.as_str(), style),
+                ]))
+            })
+            .collect();
+
+        let mut state = ListState::default();
+        state.select(Some(self.cursor.min(self.lines.len().saturating_sub(1))));
+        *state.offset_mut() = self.cursor.saturating_sub(list_area.height as usize / 2);
+        let list_area = Rect { x: list_area.x + 2, width: list_area.width.saturating_sub(4), ..list_area };
+        f.render_stateful_widget(List::new(items), list_area, &mut state);
+
+        let done = self.stamps.iter().filter(|s| s.is_some()).count();
+        let status = self.status.lock().unwrap().clone();
+        let status = if status.is_empty() {
+            format!("sync editor: {done}/{} lines stamped", self.lines.len())
+        } else {
+            status
+        };
+        f.render_widget(
+            Paragraph::new(Span::styled(status, Style::default().fg(accent)))
+                .alignment(Alignment::Center),
+            status_area,
+        );
+        f.render_widget(
+            Paragraph::new(Span::styled(HELP, Style::default().fg(dim))).alignment(Alignment::Center),
+            help_area,
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use crate::client;
+use crate::lyrics::{self, Lyrics, Query};
+use std::fmt::Write;
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Srt,
+    Vtt,
+    Ass,
+    Lrc,
+    Txt,
+    Json,
+}
+
+impl Format {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "srt" => Some(Self::Srt),
+            "vtt" | "webvtt" => Some(Self::Vtt),
+            "ass" | "ssa" => Some(Self::Ass),
+            "lrc" => Some(Self::Lrc),
+            "txt" | "plain" => Some(Self::Txt),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+struct Cue<'a> {
+    start_ms: u64,
+    end_ms: u64,
+    text: &'a str,
+}
+
+fn shift(time_ms: u64, offset_ms: i64) -> u64 {
+    (time_ms as i64 - offset_ms).max(0) as u64
+}
+
+// each cue ends where the next line starts, capped at the track duration;
+// empty lines only end the previous cue
+fn cues(lyrics: &Lyrics, duration_ms: u64, offset_ms: i64) -> Vec<Cue<'_>> {
+    let lines = &lyrics.lines;
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| !l.text.trim().is_empty())
+        .map(|(i, l)| {
+            let start_ms = shift(l.time_ms, offset_ms);
+            let next = lines.get(i + 1).map(|n| shift(n.time_ms, offset_ms));
+            let end_ms = match (next, duration_ms) {
+                (Some(n), 0) => n,
+                (Some(n), d) => n.min(d),
+                (None, 0) => start_ms + 5000,
+                (None, d) => d,
+            };
+            Cue { start_ms, end_ms: end_ms.max(start_ms), text: l.text.trim() }
+        })
+        .collect()
+}
+
+fn clock(ms: u64, frac_sep: char) -> String {
+    format!(
+        "{:02}:{:02}:{:02}{frac_sep}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+fn ass_clock(ms: u64) -> String {
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000 / 10
+    )
+}
+
+pub fn render(
+    format: Format,
+    meta: &Query,
+    lyrics: &Lyrics,
+    offset_ms: i64,
+) -> Result<String, String> {
+    let mut out = String::new();
+
+    if !lyrics.synced && !matches!(format, Format::Txt | Format::Json) {
+        return Err("no synced lyrics for this track (only txt export is possible)".into());
+    }
+
+    match format {
+        Format::Txt => {
+            for l in &lyrics.lines {
+                let _ = writeln!(out, "{}", l.text);
+            }
+        }
+        Format::Json => {
+            let lines: Vec<_> = lyrics
+                .lines
+                .iter()
+                .map(|l| lyrics::LyricLine {
+                    time_ms: shift(l.time_ms, offset_ms),
+                    text: l.text.clone(),
+                })
+                .collect();
+            let out_json = serde_json::json!({
+                "artist": meta.artist,
+                "title": meta.title,
+                "album": meta.album,
+                "duration_ms": meta.duration_ms,
+                "synced": lyrics.synced,
+                "source": lyrics.source,
+                "lines": lines,
+            });
+            let _ = writeln!(out, "{out_json}");
+        }
+        Format::Srt => {
+            for (i, c) in cues(lyrics, meta.duration_ms, offset_ms).iter().enumerate() {
+                let _ = write!(
+                    out,
+                    "{}\n{} --> {}\n{}\n\n",
+                    i + 1,
+                    clock(c.start_ms, ','),
+                    clock(c.end_ms, ','),
+                    c.text
+                );
+            }
+        }
+        Format::Vtt => {
+            out.push_str("WEBVTT\n\n");
+            for c in cues(lyrics, meta.duration_ms, offset_ms) {
+                let _ = write!(
+                    out,
+                    "{} --> {}\n{}\n\n",
+                    clock(c.start_ms, '.'),
+                    clock(c.end_ms, '.'),
+                    c.text
+                );
+            }
+        }
+        Format::Ass => {
+            let _ = write!(
+                out,
+                "[Script Info]\n\
+                 Title: {} - {}\n\
+                 ScriptType: v4.00+\n\
+                 PlayResX: 1920\n\
+                 PlayResY: 1080\n\
+                 \n\
+                 [V4+ Styles]\n\
+                 Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+                 Style: Default,Arial,64,&H00FFFFFF,&H000000FF,&H00000000,&H80000000,-1,0,0,0,100,100,0,0,1,3,1,2,60,60,80,1\n\
+                 \n\
+                 [Events]\n\
+                 Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+                meta.artist, meta.title
+            );
+            for c in cues(lyrics, meta.duration_ms, offset_ms) {
+                let text = c.text.replace('{', "(").replace('}', ")");
+                let _ = writeln!(
+                    out,
+                    "Dialogue: 0,{},{},Default,,0,0,0,,{text}",
+                    ass_clock(c.start_ms),
+                    ass_clock(c.end_ms)
+                );
+            }
+        }
+        Format::Lrc => {
+            let _ = writeln!(out, "[ar:{}]", meta.artist);
+            let _ = writeln!(out, "[ti:{}]", meta.title);
+            if meta.duration_ms > 0 {
+                let _ = writeln!(out, "[length:{}]", crate::spotify::fmt_time(meta.duration_ms));
+            }
+            for l in &lyrics.lines {
+                let ts = lyrics::lrc_timestamp(shift(l.time_ms, offset_ms));
+                let _ = writeln!(out, "{ts}{}", l.text);
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn current() -> Result<(Query, Lyrics, i64), String> {
+    let state = client::fetch_state();
+    let offset_ms = state.offset_ms();
+    let np = state.now_playing.ok_or("nothing playing right now")?;
+    let ly = state.lyrics.ok_or("no lyrics found")?;
+    let meta = Query {
+        artist: np.artist,
+        title: np.track,
+        album: np.album,
+        duration_ms: np.duration_ms,
+    };
+    Ok((meta, ly, offset_ms))
+}
+
+/// Exports the current track's lyrics, or runs the provider chain for
+/// `track` when given, to `output` or stdout.
+pub async fn run(
+    format: Format,
+    output: Option<&str>,
+    track: Option<Query>,
+    offset_ms: i64,
+) -> Result<(), String> {
+    let (meta, ly, saved_offset_ms) = match track {
+        Some(q) => {
+            let ly = lyrics::fetch(&q).await.ok_or("no lyrics found")?;
+            (q, ly, 0)
+        }
+        None => current()?,
+    };
+    let out = render(format, &meta, &ly, offset_ms + saved_offset_ms)?;
+    match output {
+        Some(path) => {
+            std::fs::write(path, out).map_err(|e| format!("failed to write {path}: {e}"))?;
+            eprintln!("wrote {path}");
+        }
+        None => print!("{out}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use crate::lyrics::{self, lrc_timestamp, normalize_name, Lyrics, Query};
+use crate::search::{self, normalize};
+use crate::spotify::fmt_time;
+use crossterm::style::Stylize;
+use rspotify::AuthCodeSpotify;
+use std::collections::HashSet;
+use std::io::{BufRead, IsTerminal, Write};
+
+pub struct Candidate {
+    pub query: Query,
+    pub lyrics: Lyrics,
+    /// Index of the best matching line.
+    pub line: usize,
+    pub score: f64,
+}
+
+// how many of the snippet's words appear in order (a longest common
+// subsequence, so other words may sit between them), as a share of the snippet
+pub fn overlap(want: &[&str], text: &str) -> f64 {
+    let words: Vec<&str> = text.split(' ').collect();
+    let mut prev = vec![0usize; words.len() + 1];
+    for w in want {
+        let mut row = vec![0usize; words.len() + 1];
+        for (j, word) in words.iter().enumerate() {
+            row[j + 1] = if w == word { prev[j] + 1 } else { prev[j + 1].max(row[j]) };
+        }
+        prev = row;
+    }
+    prev[words.len()] as f64 / want.len() as f64
+}
+
+// snippets often run across a line break, so each line is also tried joined
+// with the next one
+fn best_line(want: &[&str], ly: &Lyrics) -> Option<(usize, f64)> {
+    let lines: Vec<String> = ly.lines.iter().map(|l| normalize(&l.text)).collect();
+    let mut best: Option<(usize, f64)> = None;
+    for (i, line) in lines.iter().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let mut score = overlap(want, line);
+        if let Some(next) = lines.get(i + 1).filter(|n| !n.is_empty()) {
+            score = score.max(overlap(want, &format!("{line} {next}")) - 0.01);
+        }
+        if best.is_none_or(|(_, s)| score > s) {
+            best = Some((i, score));
+        }
+    }
+    best
+}
+
+/// Best matches for `snippet` first, one per song, synced lyrics winning ties.
+pub async fn candidates(snippet: &str) -> Result<Vec<Candidate>, String> {
+    let want_norm = normalize(snippet);
+    let want: Vec<&str> = want_norm.split(' ').filter(|w| !w.is_empty()).collect();
+    if want.is_empty() {
+        return Err("empty snippet".into());
+    }
+    let mut found: Vec<Candidate> = lyrics::search_text(snippet)
+        .await?
+        .into_iter()
+        .filter_map(|(query, lyrics)| {
+            let (line, score) = best_line(&want, &lyrics)?;
+            Some(Candidate { query, lyrics, line, score })
+        })
+        .collect();
+    found.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.lyrics.synced.cmp(&a.lyrics.synced))
+    });
+    let mut seen = HashSet::new();
+    found.retain(|c| seen.insert(normalize_name(&format!("{}{}", c.query.artist, c.query.title))));
+    Ok(found)
+}
+
+pub fn print(found: &[Candidate]) {
+    let color = std::io::stdout().is_terminal();
+    for (i, c) in found.iter().enumerate() {
+        let q = &c.query;
+        let pct = (c.score * 100.0).round();
+        println!(
+            "{:>3}. {} - {} ({}) {pct}%",
+            i + 1,
+            q.artist,
+            q.title,
+            fmt_time(q.duration_ms)
+        );
+        let l = &c.lyrics.lines[c.line];
+        let ts = if c.lyrics.synced { lrc_timestamp(l.time_ms) } else { "[--:--.--]".into() };
+        if color {
+            println!("     {} {}", ts.dark_grey(), l.text.as_str().green().bold());
+        } else {
+            println!("     {ts} {}", l.text);
+        }
+    }
+}
+
+/// Asks which candidate to play; `None` when the user gives up.
+pub fn choose(count: usize) -> Option<usize> {
+    print!("play which? [1-{count}, enter = 1, q = quit] ");
+    std::io::stdout().flush().ok()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer).ok()?;
+    match answer.trim() {
+        "" => Some(1),
+        a => a.parse().ok().filter(|n| (1..=count).contains(n)),
+    }
+}
+
+/// Starts candidate `n` (1-based) on Spotify at its matched line.
+pub async fn play(spotify: &AuthCodeSpotify, found: &[Candidate], n: usize) -> Result<(), String> {
+    let c = n
+        .checked_sub(1)
+        .and_then(|i| found.get(i))
+        .ok_or_else(|| format!("no candidate #{n}"))?;
+    let pos = if c.lyrics.synced { c.lyrics.lines[c.line].time_ms } else { 0 };
+    search::play_at(spotify, &c.query.artist, &c.query.title, pos).await
+}
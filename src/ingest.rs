@@ -0,0 +1,142 @@
+use crate::player::{BoxFuture, Control, PlayerBackend};
+use crate::spotify::{fmt_time, DeviceInfo, NowPlaying};
+use serde::Deserialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// a pushed track nobody has updated for this long is dropped
+const STALE: Duration = Duration::from_secs(300);
+// and so is one that's played past its end without a new one arriving
+const PAST_END_MS: u64 = 5000;
+
+/// A `set_now_playing` message: a new track when it has an artist and a
+/// title, else a heartbeat for the pushed one.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct Update {
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    pub album: Option<String>,
+    pub duration_ms: Option<u64>,
+    pub position_ms: Option<u64>,
+    pub playing: Option<bool>,
+    pub album_art: Option<String>,
+    /// Name the player counts as for device offsets.
+    pub player: Option<String>,
+}
+
+struct Pushed {
+    np: NowPlaying,
+    at: Instant,
+}
+
+impl Pushed {
+    fn elapsed_ms(&self) -> u64 {
+        match self.np.is_playing {
+            true => self.at.elapsed().as_millis() as u64,
+            false => 0,
+        }
+    }
+
+    fn stale(&self) -> bool {
+        let end = self.np.duration_ms;
+        self.at.elapsed() > STALE
+            || (end > 0 && self.np.progress_ms + self.elapsed_ms() > end + PAST_END_MS)
+    }
+
+    // with the position moved on to now
+    fn current(&self) -> NowPlaying {
+        let mut np = self.np.clone();
+        np.progress_ms += self.elapsed_ms();
+        if np.duration_ms > 0 {
+            np.progress_ms = np.progress_ms.min(np.duration_ms);
+        }
+        np.progress = fmt_time(np.progress_ms);
+        np
+    }
+}
+
+/// Now-playing state pushed in by scripts (`lirik ingest`), for players
+/// lirik can't follow itself. Always one of the daemon's sources, ahead of
+/// the configured players.
+#[derive(Default)]
+pub struct Ingest {
+    pushed: Mutex<Option<Pushed>>,
+}
+
+impl Ingest {
+    fn active(&self) -> Option<NowPlaying> {
+        let mut pushed = self.pushed.lock().unwrap();
+        if pushed.as_ref().is_some_and(Pushed::stale) {
+            *pushed = None;
+        }
+        pushed.as_ref().map(Pushed::current)
+    }
+
+    pub fn set(&self, update: Update) -> Result<(), String> {
+        let mut pushed = self.pushed.lock().unwrap();
+        let mut np = match (update.artist, update.title) {
+            (Some(artist), Some(title)) => {
+                let duration_ms = update.duration_ms.unwrap_or(0);
+                NowPlaying {
+                    // offsets are saved per track, so give it a stable id
+                    id: Some(format!("ingest:{artist} - {title}")),
+                    artist,
+                    track: title,
+                    album: update.album.unwrap_or_default(),
+                    album_art: update.album_art,
+                    popularity: 0,
+                    explicit: false,
+                    spotify_url: None,
+                    progress_ms: 0,
+                    progress: String::new(),
+                    duration_ms,
+                    duration: fmt_time(duration_ms),
+                    is_playing: true,
+                    device: Some(DeviceInfo {
+                        id: None,
+                        name: update.player.unwrap_or_else(|| "ingest".into()),
+                        device_type: "ingest".into(),
+                        volume: None,
+                    }),
+                    shuffle: false,
+                    repeat: "off".into(),
+                    context: None,
+                }
+            }
+            (None, None) => {
+                let current = pushed.as_ref().filter(|p| !p.stale()).map(Pushed::current);
+                let mut np = current.ok_or("nothing pushed yet, send an artist and title first")?;
+                if let Some(ms) = update.duration_ms {
+                    np.duration_ms = ms;
+                    np.duration = fmt_time(ms);
+                }
+                np
+            }
+            _ => return Err("artist and title go together".into()),
+        };
+        if let Some(ms) = update.position_ms {
+            np.progress_ms = ms;
+        }
+        if let Some(playing) = update.playing {
+            np.is_playing = playing;
+        }
+        np.progress = fmt_time(np.progress_ms);
+        *pushed = Some(Pushed { np, at: Instant::now() });
+        Ok(())
+    }
+
+    pub fn clear(&self) {
+        *self.pushed.lock().unwrap() = None;
+    }
+}
+
+impl PlayerBackend for Ingest {
+    fn now_playing(&self) -> BoxFuture<'_, Option<NowPlaying>> {
+        Box::pin(async move { self.active() })
+    }
+
+    fn control(&self, _control: Control) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(async { Err("playback is pushed in from another player, control it there".into()) })
+    }
+}
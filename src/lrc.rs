@@ -0,0 +1,251 @@
+use crate::lyrics::{self, lrc_timestamp, LyricLine};
+use crate::spotify::{fmt_time, parse_time};
+
+// informational ID tags; parse_lrc skips them, which is fine
+const ID_TAGS: &[&str] = &[
+    "ar", "al", "ti", "au", "by", "length", "re", "ve", "tool", "la", "lang", "#",
+];
+
+pub struct Issue {
+    pub line: usize,
+    pub msg: String,
+}
+
+// splits the leading `[..]` groups off a line
+fn split_tags(line: &str) -> (Vec<&str>, &str) {
+    let mut tags = Vec::new();
+    let mut rest = line;
+    while let Some(inner) = rest.strip_prefix('[') {
+        let Some((tag, after)) = inner.split_once(']') else { break };
+        tags.push(tag);
+        rest = after;
+    }
+    (tags, rest)
+}
+
+fn id_tag(tag: &str) -> Option<(String, &str)> {
+    let (key, val) = tag.split_once(':')?;
+    let first = key.chars().next()?;
+    (first.is_alphabetic() || first == '#').then(|| (key.trim().to_lowercase(), val.trim()))
+}
+
+// accepts what the parser accepts, plus "mm:ss" and "mm:ss:xx"
+fn loose_timestamp(s: &str) -> Option<u64> {
+    if let Some(t) = lyrics::parse_timestamp(s) {
+        return Some(t);
+    }
+    let mut parts = s.split([':', '.']);
+    let min: u64 = parts.next()?.trim().parse().ok()?;
+    let sec: u64 = parts.next()?.trim().parse().ok()?;
+    let frac = match parts.next() {
+        Some(f) => lyrics::parse_timestamp(&format!("0:0.{}", f.trim()))?,
+        None => 0,
+    };
+    parts.next().is_none().then_some(min * 60000 + sec * 1000 + frac)
+}
+
+fn is_canonical(s: &str) -> bool {
+    let Some((_, rest)) = s.split_once(':') else { return false };
+    let Some((sec, frac)) = rest.split_once('.') else { return false };
+    sec.len() == 2 && sec.parse::<u64>().is_ok_and(|s| s < 60) && (2..=3).contains(&frac.len())
+}
+
+fn length_tag(src: &str) -> Option<u64> {
+    src.lines().find_map(|line| {
+        let (tags, _) = split_tags(line.trim());
+        let (key, val) = id_tag(tags.first()?)?;
+        if key != "length" {
+            return None;
+        }
+        let whole = val.split('.').next().unwrap_or(val);
+        parse_time(whole)
+    })
+}
+
+pub fn check(src: &str, duration_ms: Option<u64>) -> Vec<Issue> {
+    let limit = duration_ms.or_else(|| length_tag(src));
+    let mut issues = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    let mut last = 0u64;
+    let mut report = |line: usize, msg: String| issues.push(Issue { line, msg });
+
+    let lines: Vec<&str> = src.lines().collect();
+    let content_end = lines.iter().rposition(|l| !l.trim().is_empty()).map_or(0, |i| i + 1);
+    for n in content_end..lines.len() {
+        report(n + 1, "empty trailing line".into());
+    }
+
+    for (i, raw) in lines[..content_end].iter().enumerate() {
+        let n = i + 1;
+        if raw.trim().is_empty() {
+            continue;
+        }
+        if raw.starts_with(char::is_whitespace) {
+            report(n, "leading whitespace; the line is ignored".into());
+        }
+        let (tags, text) = split_tags(raw.trim());
+        let Some(first) = tags.first() else {
+            report(n, "no timestamp; the line is ignored".into());
+            continue;
+        };
+
+        if let Some((key, val)) = id_tag(first) {
+            if key == "offset" {
+                report(n, format!("[offset:{val}] is not applied by lirik; timings are shifted"));
+            } else if !ID_TAGS.contains(&key.as_str()) {
+                report(n, format!("unknown tag [{key}:]"));
+            }
+            continue;
+        }
+
+        if tags.len() > 1 {
+            report(n, format!("{} timestamps on one line; only the first is used", tags.len()));
+        }
+        for (t, tag) in tags.iter().enumerate() {
+            let Some(time) = loose_timestamp(tag) else {
+                report(n, format!("malformed timestamp [{tag}]"));
+                continue;
+            };
+            if lyrics::parse_timestamp(tag).is_none() {
+                let what = if t == 0 { "the line is ignored" } else { "the time is ignored" };
+                report(n, format!("malformed timestamp [{tag}]; {what}"));
+            } else if !is_canonical(tag) {
+                report(n, format!("non-standard timestamp [{tag}], expected [mm:ss.xx]"));
+            }
+            if time < last {
+                let (time, last) = (lrc_timestamp(time), lrc_timestamp(last));
+                report(n, format!("out of order: {time} comes after {last}"));
+            }
+            if !seen.insert(time) {
+                report(n, format!("duplicate timestamp {}", lrc_timestamp(time)));
+            }
+            if let Some(limit) = limit
+                && time > limit
+            {
+                let (time, limit) = (lrc_timestamp(time), fmt_time(limit));
+                report(n, format!("{time} is past the track length {limit}"));
+            }
+            last = last.max(time);
+        }
+        if text.contains('<') && text.contains('>') {
+            report(n, "word timing tags are shown as text".into());
+        }
+    }
+
+    issues.sort_by_key(|i| i.line);
+    issues
+}
+
+/// Rewrites `src` as canonical LRC: ID tags first, one timestamp per line in
+/// time order, `[offset:]` applied, duplicates merged, trailing blanks removed.
+/// Also returns what it had to leave out: untimed lines and bad timestamps.
+pub fn normalize(src: &str) -> (String, Vec<Issue>) {
+    let mut tags = Vec::new();
+    let mut offset = 0i64;
+    let mut lines: Vec<LyricLine> = Vec::new();
+    let mut dropped = Vec::new();
+
+    for (i, raw) in src.lines().enumerate() {
+        let (heads, text) = split_tags(raw.trim());
+        let Some(first) = heads.first() else {
+            if !raw.trim().is_empty() {
+                let msg = format!("no timestamp; dropped \"{}\"", raw.trim());
+                dropped.push(Issue { line: i + 1, msg });
+            }
+            continue;
+        };
+        if let Some((key, val)) = id_tag(first) {
+            if key == "offset" {
+                offset = val.parse().unwrap_or(0);
+            } else {
+                tags.push(format!("[{key}:{val}]"));
+            }
+            continue;
+        }
+        let times: Vec<Option<u64>> = heads.iter().map(|h| loose_timestamp(h)).collect();
+        let timed = times.iter().any(Option::is_some);
+        for (head, time) in heads.iter().zip(times) {
+            match time {
+                Some(time_ms) => lines.push(LyricLine { time_ms, text: text.trim().to_string() }),
+                None => {
+                    let what = match timed {
+                        true => "the time".to_string(),
+                        false => format!("\"{}\"", raw.trim()),
+                    };
+                    let msg = format!("malformed timestamp [{head}]; dropped {what}");
+                    dropped.push(Issue { line: i + 1, msg });
+                }
+            }
+        }
+    }
+
+    // positive offsets show lyrics earlier
+    for l in &mut lines {
+        l.time_ms = (l.time_ms as i64 - offset).max(0) as u64;
+    }
+    lines.sort_by_key(|l| l.time_ms);
+
+    let mut merged: Vec<LyricLine> = Vec::with_capacity(lines.len());
+    for l in lines {
+        match merged.last_mut() {
+            Some(prev) if prev.time_ms == l.time_ms => {
+                if prev.text.is_empty() {
+                    prev.text = l.text;
+                } else if !l.text.is_empty() && prev.text != l.text {
+                    prev.text = format!("{} / {}", prev.text, l.text);
+                }
+            }
+            _ => merged.push(l),
+        }
+    }
+    // keep a single empty line at the end as the end marker of the last line
+    while merged.len() >= 2
+        && merged[merged.len() - 1].text.is_empty()
+        && merged[merged.len() - 2].text.is_empty()
+    {
+        merged.pop();
+    }
+
+    let mut out = String::new();
+    for tag in tags {
+        out.push_str(&tag);
+        out.push('\n');
+    }
+    for l in merged {
+        out.push_str(&lrc_timestamp(l.time_ms));
+        out.push_str(&l.text);
+        out.push('\n');
+    }
+    (out, dropped)
+}
+
+/// Lints `path`, optionally rewriting it first. Returns whether it is clean.
+pub fn run(path: &str, duration_ms: Option<u64>, fix: bool) -> Result<bool, String> {
+    let mut src =
+        std::fs::read_to_string(path).map_err(|e| format!("failed to read {path}: {e}"))?;
+
+    // line numbers here are from the file as it was before the rewrite
+    let mut dropped = Vec::new();
+    if fix {
+        let (fixed, lost) = normalize(&src);
+        if fixed != src {
+            std::fs::write(path, &fixed).map_err(|e| format!("failed to write {path}: {e}"))?;
+            eprintln!("wrote {path}");
+        }
+        src = fixed;
+        dropped = lost;
+    }
+    for issue in &dropped {
+        println!("{path}:{} (before --fix): {}", issue.line, issue.msg);
+    }
+
+    let issues = check(&src, duration_ms);
+    for issue in &issues {
+        println!("{path}:{}: {}", issue.line, issue.msg);
+    }
+    let clean = issues.is_empty() && dropped.is_empty();
+    if clean {
+        eprintln!("{path}: ok");
+    }
+    Ok(clean)
+}
@@ -1,4 +1,6 @@
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct LyricLine {
@@ -10,6 +12,30 @@ pub struct LyricLine {
 pub struct Lyrics {
     pub synced: bool,
     pub lines: Vec<LyricLine>,
+    #[serde(default)]
+    pub source: String,
+}
+
+impl Lyrics {
+    fn from_lrc(lrc: &str, source: &str) -> Option<Self> {
+        let lines = parse_lrc(lrc);
+        (!lines.is_empty()).then(|| Self { synced: true, lines, source: source.into() })
+    }
+
+    fn from_plain(text: &str, source: &str) -> Self {
+        let lines = text
+            .lines()
+            .map(|l| LyricLine { time_ms: 0, text: l.to_string() })
+            .collect();
+        Self { synced: false, lines, source: source.into() }
+    }
+}
+
+pub struct Query {
+    pub artist: String,
+    pub title: String,
+    pub album: String,
+    pub duration_ms: u64,
 }
 
 #[derive(Deserialize)]
@@ -18,17 +44,119 @@ struct LrcLibResponse {
     synced_lyrics: Option<String>,
     #[serde(rename = "plainLyrics")]
     plain_lyrics: Option<String>,
+    #[serde(default)]
+    duration: f64,
+    #[serde(rename = "artistName", default)]
+    artist_name: String,
+    #[serde(rename = "trackName", default)]
+    track_name: String,
+    #[serde(rename = "albumName", default)]
+    album_name: Option<String>,
+}
+
+impl LrcLibResponse {
+    fn into_lyrics(self, source: &str) -> Option<Lyrics> {
+        self.synced_lyrics
+            .and_then(|s| Lyrics::from_lrc(&s, source))
+            .or_else(|| Some(Lyrics::from_plain(&self.plain_lyrics?, source)))
+    }
+}
+
+const LRCLIB: &str = "https://lrclib.net/api";
+const USER_AGENT: &str = "lirik/0.1.0";
+
+/// Runs the provider chain: your own synced overrides, local `.lrc` files, the
+/// lyrics cache, then LRCLIB. Anything fetched from LRCLIB is written to the cache.
+pub async fn fetch(q: &Query) -> Option<Lyrics> {
+    if let Some(ly) = saved_override(q)
+        .or_else(|| local(q))
+        .or_else(|| crate::cache::load(q))
+    {
+        return Some(ly);
+    }
+    let ly = fetch_remote(q).await?;
+    crate::cache::store(q, &ly);
+    Some(ly)
+}
+
+/// LRCLIB's exact match, then LRCLIB search. A plain-text hit is only kept if
+/// nothing synced turns up.
+pub async fn fetch_remote(q: &Query) -> Option<Lyrics> {
+    let exact = lrclib_get(q).await;
+    if exact.as_ref().is_some_and(|ly| ly.synced) {
+        return exact;
+    }
+    match lrclib_search(q).await {
+        Some(ly) if ly.synced || exact.is_none() => Some(ly),
+        _ => exact,
+    }
+}
+
+pub fn normalize_name(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+pub fn overrides_dir() -> PathBuf {
+    crate::config::data_dir().join("overrides")
 }
 
-pub async fn fetch(artist: &str, track: &str, duration_ms: u64) -> Option<Lyrics> {
+pub fn override_path(q: &Query) -> PathBuf {
+    let name = format!("{}-{}.lrc", normalize_name(&q.artist), normalize_name(&q.title));
+    overrides_dir().join(name)
+}
+
+fn saved_override(q: &Query) -> Option<Lyrics> {
+    let contents = std::fs::read_to_string(override_path(q)).ok()?;
+    Lyrics::from_lrc(&contents, "override")
+}
+
+/// Reads an `.lrc` file, falling back to plain text when it has no timestamps.
+pub fn from_file(contents: &str, source: &str) -> Lyrics {
+    Lyrics::from_lrc(contents, source).unwrap_or_else(|| {
+        let text: Vec<&str> = contents.lines().filter(|l| !l.starts_with('[')).collect();
+        Lyrics::from_plain(&text.join("\n"), source)
+    })
+}
+
+// looks for "<artist> - <title>.lrc" in the configured lyrics_dirs
+fn local(q: &Query) -> Option<Lyrics> {
+    let dirs = crate::config::load()?.lyrics_dirs;
+    let want = normalize_name(&format!("{}{}", q.artist, q.title));
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(crate::config::expand(&dir)) else { continue };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|e| !e.eq_ignore_ascii_case("lrc")) {
+                continue;
+            }
+            let stem = path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+            if normalize_name(&stem) != want {
+                continue;
+            }
+            // e.g. not UTF-8; another file or dir may still have it
+            let Ok(contents) = std::fs::read_to_string(&path) else { continue };
+            return Some(from_file(&contents, "local"));
+        }
+    }
+    None
+}
+
+async fn lrclib_get(q: &Query) -> Option<Lyrics> {
+    let duration = (q.duration_ms / 1000).to_string();
+    let mut query = vec![("artist_name", q.artist.as_str()), ("track_name", q.title.as_str())];
+    if !q.album.is_empty() {
+        query.push(("album_name", &q.album));
+    }
+    if q.duration_ms > 0 {
+        query.push(("duration", &duration));
+    }
     let resp = reqwest::Client::new()
-        .get("https://lrclib.net/api/get")
-        .header("User-Agent", "lirik/0.1.0")
-        .query(&[
-            ("artist_name", artist),
-            ("track_name", track),
-            ("duration", &(duration_ms / 1000).to_string()),
-        ])
+        .get(format!("{LRCLIB}/get"))
+        .header("User-Agent", USER_AGENT)
+        .query(&query)
         .send()
         .await
         .ok()?;
@@ -38,46 +166,158 @@ pub async fn fetch(artist: &str, track: &str, duration_ms: u64) -> Option<Lyrics
     }
 
     let data: LrcLibResponse = resp.json().await.ok()?;
+    data.into_lyrics("lrclib")
+}
 
-    if let Some(ref synced) = data.synced_lyrics {
-        let lines = parse_lrc(synced);
-        if !lines.is_empty() {
-            return Some(Lyrics { synced: true, lines });
-        }
+// picks the closest-length result, preferring synced lyrics
+async fn lrclib_search(q: &Query) -> Option<Lyrics> {
+    let resp = reqwest::Client::new()
+        .get(format!("{LRCLIB}/search"))
+        .header("User-Agent", USER_AGENT)
+        .query(&[("artist_name", q.artist.as_str()), ("track_name", q.title.as_str())])
+        .send()
+        .await
+        .ok()?;
+
+    if !resp.status().is_success() {
+        return None;
+    }
+
+    let results: Vec<LrcLibResponse> = resp.json().await.ok()?;
+    let target = q.duration_ms as f64 / 1000.0;
+    results
+        .into_iter()
+        .filter(|r| q.duration_ms == 0 || (r.duration - target).abs() <= 3.0)
+        .min_by_key(|r| {
+            let unsynced = r.synced_lyrics.is_none() as u64;
+            (unsynced, ((r.duration - target).abs() * 1000.0) as u64)
+        })?
+        .into_lyrics("lrclib")
+}
+
+/// LRCLIB's full-text search, which also looks inside the lyrics.
+pub async fn search_text(text: &str) -> Result<Vec<(Query, Lyrics)>, String> {
+    let resp = reqwest::Client::new()
+        .get(format!("{LRCLIB}/search"))
+        .header("User-Agent", USER_AGENT)
+        .query(&[("q", text)])
+        .send()
+        .await
+        .map_err(|e| format!("lrclib: {e}"))?;
+    if !resp.status().is_success() {
+        return Err(format!("lrclib: {}", resp.status()));
     }
+    let results: Vec<LrcLibResponse> = resp.json().await.map_err(|e| format!("lrclib: {e}"))?;
+    Ok(results
+        .into_iter()
+        .filter_map(|r| {
+            let q = Query {
+                artist: r.artist_name.clone(),
+                title: r.track_name.clone(),
+                album: r.album_name.clone().unwrap_or_default(),
+                duration_ms: (r.duration * 1000.0) as u64,
+            };
+            Some((q, r.into_lyrics("lrclib")?))
+        })
+        .collect())
+}
+
+#[derive(Deserialize)]
+struct Challenge {
+    prefix: String,
+    target: String,
+}
 
-    let plain = data.plain_lyrics?;
-    let lines = plain
-        .lines()
-        .map(|l| LyricLine { time_ms: 0, text: l.to_string() })
+// LRCLIB's proof of work: a nonce whose sha256(prefix + nonce) is <= target
+fn solve_challenge(c: &Challenge) -> Option<u64> {
+    let target = (0..c.target.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(c.target.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    (0u64..).find(|nonce| Sha256::digest(format!("{}{nonce}", c.prefix)).as_slice() <= &target[..])
+}
+
+/// Publishes synced lyrics to LRCLIB. Solving the challenge takes a while.
+pub async fn publish(q: &Query, ly: &Lyrics) -> Result<(), String> {
+    let client = reqwest::Client::new();
+    let challenge: Challenge = client
+        .post(format!("{LRCLIB}/request-challenge"))
+        .header("User-Agent", USER_AGENT)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let prefix = challenge.prefix.clone();
+    let nonce = tokio::task::spawn_blocking(move || solve_challenge(&challenge))
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("bad publish challenge")?;
+
+    let plain: Vec<&str> = ly.lines.iter().map(|l| l.text.as_str()).collect();
+    let synced: String = ly
+        .lines
+        .iter()
+        .map(|l| format!("{}{}\n", lrc_timestamp(l.time_ms), l.text))
         .collect();
-    Some(Lyrics { synced: false, lines })
+    let body = serde_json::json!({
+        "trackName": q.title,
+        "artistName": q.artist,
+        "albumName": q.album,
+        "duration": q.duration_ms / 1000,
+        "plainLyrics": plain.join("\n"),
+        "syncedLyrics": synced,
+    });
+    let resp = client
+        .post(format!("{LRCLIB}/publish"))
+        .header("User-Agent", USER_AGENT)
+        .header("X-Publish-Token", format!("{prefix}:{nonce}"))
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if resp.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("publish failed: {}", resp.status()))
+    }
 }
 
-fn parse_lrc(lrc: &str) -> Vec<LyricLine> {
+/// Parses an LRC time tag body like "01:23.45" into milliseconds.
+pub fn parse_timestamp(s: &str) -> Option<u64> {
+    let (min_str, rest) = s.split_once(':')?;
+    let (sec_str, frac_str) = rest.split_once('.')?;
+    let min: u64 = min_str.parse().ok()?;
+    let sec: u64 = sec_str.parse().ok()?;
+    let frac: u64 = frac_str.parse().ok()?;
+    let frac_ms = match frac_str.len() {
+        1 => frac * 100,
+        2 => frac * 10,
+        3 => frac,
+        _ => frac,
+    };
+    Some(min * 60000 + sec * 1000 + frac_ms)
+}
+
+pub fn parse_lrc(lrc: &str) -> Vec<LyricLine> {
     lrc.lines()
         .filter_map(|line| {
             let rest = line.strip_prefix('[')?;
             let (time_str, text) = rest.split_once(']')?;
-            let (min_str, rest) = time_str.split_once(':')?;
-            let (sec_str, frac_str) = rest.split_once('.')?;
-            let min: u64 = min_str.parse().ok()?;
-            let sec: u64 = sec_str.parse().ok()?;
-            let frac: u64 = frac_str.parse().ok()?;
-            let frac_ms = match frac_str.len() {
-                1 => frac * 100,
-                2 => frac * 10,
-                3 => frac,
-                _ => frac,
-            };
             Some(LyricLine {
-                time_ms: min * 60000 + sec * 1000 + frac_ms,
+                time_ms: parse_timestamp(time_str)?,
                 text: text.trim().to_string(),
             })
         })
         .collect()
 }
 
+pub fn lrc_timestamp(ms: u64) -> String {
+    format!("[{:02}:{:02}.{:02}]", ms / 60000, ms / 1000 % 60, ms % 1000 / 10)
+}
+
 pub fn current_line_index(lines: &[LyricLine], progress_ms: u64) -> Option<usize> {
     if lines.is_empty() || lines[0].time_ms > progress_ms {
         return None;
@@ -92,3 +332,70 @@ pub fn current_line_index(lines: &[LyricLine], progress_ms: u64) -> Option<usize
     }
     Some(idx)
 }
+
+// shortest run of lines treated as a repeated block (chorus)
+const MIN_BLOCK: usize = 2;
+
+fn repeated_block(runs: &[(&str, usize)], i: usize) -> Option<(usize, usize)> {
+    let mut best: Option<(usize, usize)> = None;
+    for j in 0..i {
+        let len = (0..)
+            .take_while(|&m| {
+                j + m < i
+                    && i + m < runs.len()
+                    && !runs[i + m].0.is_empty()
+                    && runs[j + m] == runs[i + m]
+            })
+            .count();
+        if len >= MIN_BLOCK && best.is_none_or(|(_, l)| len > l) {
+            best = Some((j, len));
+        }
+    }
+    best
+}
+
+/// Collapses consecutive duplicate lines into "line (x3)" and replaces blocks
+/// that already appeared earlier with a "[Chorus]" reference.
+pub fn compact(lines: &[LyricLine]) -> Vec<String> {
+    let mut runs: Vec<(&str, usize)> = Vec::new();
+    for line in lines {
+        let text = line.text.trim();
+        match runs.last_mut() {
+            Some((prev, n)) if *prev == text => *n += 1,
+            _ => runs.push((text, 1)),
+        }
+    }
+
+    let mut blocks: Vec<(usize, usize)> = Vec::new();
+    let mut out: Vec<(String, usize)> = Vec::new();
+    let mut i = 0;
+    while i < runs.len() {
+        if let Some(block) = repeated_block(&runs, i) {
+            let n = match blocks.iter().position(|b| *b == block) {
+                Some(n) => n,
+                None => {
+                    blocks.push(block);
+                    blocks.len() - 1
+                }
+            };
+            let label = if n == 0 {
+                "[Chorus]".to_string()
+            } else {
+                format!("[Chorus {}]", n + 1)
+            };
+            match out.last_mut() {
+                Some((prev, count)) if *prev == label => *count += 1,
+                _ => out.push((label, 1)),
+            }
+            i += block.1;
+        } else {
+            let (text, n) = runs[i];
+            out.push((text.to_string(), if text.is_empty() { 1 } else { n }));
+            i += 1;
+        }
+    }
+
+    out.into_iter()
+        .map(|(text, n)| if n > 1 { format!("{text} (x{n})") } else { text })
+        .collect()
+}
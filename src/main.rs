@@ -1,15 +1,41 @@
 mod auth;
+mod cache;
+mod calibrate;
+mod card;
 mod client;
+mod cloze;
 mod config;
 mod daemon;
+mod draw;
+mod editor;
+mod export;
+mod find;
+mod ingest;
+mod lrc;
 mod lyrics;
+mod mpd;
+mod mpris;
+mod offsets;
+mod play;
+mod player;
+mod profanity;
+mod quotes;
+mod search;
+mod sources;
 mod spotify;
+mod sync;
+mod tag;
 mod tui;
+mod video;
 mod watch;
 mod web;
 
+use player::PlayerBackend;
 use rspotify::{scopes, AuthCodeSpotify, Config, Credentials, OAuth};
+use sources::Source;
 use std::env;
+use std::io::IsTerminal;
+use std::sync::Arc;
 
 fn make_client() -> AuthCodeSpotify {
     config::apply_env();
@@ -19,7 +45,9 @@ fn make_client() -> AuthCodeSpotify {
     let oauth = OAuth::from_env(scopes!(
         "user-read-currently-playing",
         "user-read-playback-state",
-        "user-modify-playback-state"
+        "user-modify-playback-state",
+        "playlist-read-private",
+        "playlist-read-collaborative"
     ))
     .expect("set RSPOTIFY_REDIRECT_URI=http://127.0.0.1:8888/callback");
 
@@ -32,6 +60,27 @@ fn make_client() -> AuthCodeSpotify {
     AuthCodeSpotify::with_config(creds, oauth, config)
 }
 
+async fn make_players(cfg: &config::Config) -> Vec<Source> {
+    let mut players = Vec::new();
+    for backend in cfg.backends() {
+        let player: Arc<dyn PlayerBackend> = match backend {
+            config::Backend::Spotify => {
+                let spotify = make_client();
+                auth::authenticate(&spotify).await;
+                Arc::new(spotify)
+            }
+            config::Backend::Mpris => Arc::new(or_exit(mpris::Mpris::connect(&cfg.mpris_player).await)),
+            config::Backend::Mpd => {
+                Arc::new(or_exit(mpd::Mpd::connect(&cfg.mpd_address, &cfg.mpd_password).await))
+            }
+            // the daemon always takes pushed-in state
+            config::Backend::Ingest => continue,
+        };
+        players.push(Source { name: backend.name().into(), player });
+    }
+    players
+}
+
 fn has(args: &[String], short: char, long: &str) -> bool {
     args.iter().any(|a| {
         a == long
@@ -52,12 +101,45 @@ fn parse_web_port(args: &[String]) -> u16 {
         .unwrap_or_else(|| config::load().map(|c| c.web_port).unwrap_or(0))
 }
 
+fn arg_value<'a>(args: &'a [String], names: &[&str]) -> Option<&'a str> {
+    args.iter()
+        .position(|a| names.contains(&a.as_str()))
+        .and_then(|i| args.get(i + 1))
+        .map(|s| s.as_str())
+}
+
+fn parse_query(args: &[String]) -> Option<lyrics::Query> {
+    Some(lyrics::Query {
+        artist: arg_value(args, &["--artist"])?.to_string(),
+        title: arg_value(args, &["--title"])?.to_string(),
+        album: arg_value(args, &["--album"]).unwrap_or_default().to_string(),
+        duration_ms: arg_value(args, &["--duration"])
+            .and_then(spotify::parse_time)
+            .unwrap_or(0),
+    })
+}
+
+fn config_offset() -> i64 {
+    config::load().map(|c| c.lyrics_offset_ms).unwrap_or(0)
+}
+
 fn parse_offset(args: &[String]) -> i64 {
     args.iter()
         .position(|a| a == "--offset" || a == "-o")
         .and_then(|i| args.get(i + 1))
         .and_then(|s| s.parse().ok())
-        .unwrap_or_else(|| config::load().map(|c| c.lyrics_offset_ms).unwrap_or(0))
+        .unwrap_or_else(config_offset)
+}
+
+fn parse_preset(args: &[String]) -> card::Preset {
+    arg_value(args, &["--preset"])
+        .map(|p| {
+            card::Preset::parse(p).unwrap_or_else(|| {
+                eprintln!("unknown preset: {p} (square, story, wide)");
+                std::process::exit(1);
+            })
+        })
+        .unwrap_or(card::Preset::Square)
 }
 
 fn print_cmd_result(result: &Result<String, String>) {
@@ -70,6 +152,13 @@ fn print_cmd_result(result: &Result<String, String>) {
     }
 }
 
+fn or_exit<T>(result: Result<T, String>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    })
+}
+
 fn run_async(f: impl std::future::Future<Output = ()>) {
     tokio::runtime::Runtime::new().unwrap().block_on(f);
 }
@@ -92,6 +181,7 @@ options:
   -p, --plain           print all lyrics to stdout
   -c, --current         with -p: from current line to end
   -r, --reverse         with -p: reverse output order
+  --compact             with -p: collapse repeated lines and choruses
   -w, --watch           stream lyrics line by line as they play
   -o, --offset <ms>     shift lyrics timing (positive = earlier)
   --web [port]          enable web UI (default port: 3000)
@@ -100,6 +190,7 @@ options:
 
 playback:
   play, toggle          toggle play/pause
+  play <search>         search Spotify and start a track, album or playlist (see below)
   pause                 pause playback
   next                  skip to next track
   prev                  go to previous track
@@ -109,19 +200,107 @@ commands:
   auth                  show auth & credential status
   auth login            open browser to authenticate with Spotify
   config                create/show config (~/.config/lirik/config.toml)
+  calibrate             measure the active device's latency (--track <uri>, --reset)
+  card                  render the current line as a shareable PNG (see below)
+  export                export synced lyrics as subtitles (see below)
+  find <snippet>        identify a song from a remembered line and play it there
+  ingest                push now-playing state from another player (see below)
+  lyrics get            look up lyrics for any artist/title (see below)
+  lyrics sync-playlist <uri>
+  lyrics sync-album <uri>
+                        download lyrics for every track (see below)
+  lyrics tag <dir>      write lyrics into local audio file tags (see below)
+  lyrics search <phrase>
+                        search cached and local lyrics (see below)
+  lrc check <file>      lint an .lrc file (--duration <m:ss>, --fix)
+  loop <from> <to>      repeat a range of lyric lines (--times <n>, off to stop)
+  devices               list Spotify Connect devices
+  device <name|n>       move playback to a device
+  queue                 list upcoming tracks
+  queue add <uri|search>
+                        add a track (or the top search hit) to the queue
+  quote                 save the current line to your quotes journal (see below)
+  quotes                list, search and export saved quotes (see below)
   restart               kill and restart daemon in foreground
+  source list           players the daemon follows, and which one is shown
+  source use <name>     show only that player (auto to go back to source_priority)
   stop                  kill daemon
+  video                 render a lyric clip as an animated GIF/APNG (see below)
   --daemon              start background daemon (auto-started normally)
   -h, --help            show this help
+
+export:
+  lirik export --format srt|vtt|ass|lrc|txt|json [-o file] [--offset <ms>]
+               [--artist <name> --title <name> [--album <name>] [--duration <m:ss>]]
+                        current track, or fetch by artist/title;
+                        format is guessed from -o when omitted
+
+card:
+  lirik card [-o out.png] [--preset square|story|wide] [--quote <n>] [--offset <ms>]
+                        album art, lyric and credit on a background picked
+                        from the artwork; --quote uses saved quote n
+
+video:
+  lirik video [--from <m:ss>] [--to <m:ss>] [-o clip.gif|clip.png]
+              [--preset square|story|wide] [--offset <ms>]
+                        animated GIF or APNG of the current track's lyrics
+                        scrolling by in time; --to defaults to 30s after --from
+
+ingest:
+  lirik ingest --artist <name> --title <name> [--album <name>] [--duration <ms>]
+               [--position <ms>] [--playing|--paused] [--player <name>] [--art <url>]
+                        serve this track as now playing, with lyrics
+  lirik ingest --position <ms> [--playing|--paused]
+                        heartbeat for the pushed track
+  lirik ingest --clear  back to the configured player
+
+quotes:
+  lirik quote [--prev <n>] [--next <n>] [--offset <ms>]
+                        save the current line, plus n lines before/after
+  lirik quotes [--search <text>] [--format txt|md|json] [-o file]
+                        list saved quotes, filtered by artist/title/text
+
+play:
+  lirik play \"artist - song\" [--album|--playlist] [-y] [--limit <n>] [--pick <n>] [--list]
+                        show the top Spotify matches and start the chosen one
+                        on the active device; -y plays the best match
+
+find:
+  lirik find <lyric snippet> [--limit <n>] [--pick <n>] [--list]
+                        search LRCLIB inside lyrics, show the best matches
+                        and play the chosen one from the matching line
+
+lyrics:
+  lirik lyrics get --artist <name> --title <name> [--album <name>]
+                   [--duration <m:ss>] [--format txt|lrc|json] [-o file]
+                        look up lyrics directly, no Spotify or daemon needed
+  lirik lyrics sync-playlist|sync-album <uri> [--dir <path>] [--no-cache] [--jobs <n>]
+                        fetch lyrics for a whole playlist or album into the
+                        lyrics cache and/or .lrc files in --dir (default 4 jobs)
+  lirik lyrics tag <dir> [--dry-run] [--skip-existing] [--sylt]
+                        tag mp3/flac/ogg/opus/m4a files from their artist/title;
+                        LRC goes in USLT (mp3), LYRICS (vorbis) or ©lyr (mp4),
+                        --sylt also writes an ID3v2 SYLT frame
+  lirik lyrics search <phrase> [--play [n]] [--json]
+                        find lines in cached, override and local lyrics;
+                        --play starts result n (default 1) at that line
 "
             );
         }
         Some("--daemon") => run_async(async {
-            let spotify = make_client();
-            auth::authenticate(&spotify).await;
-            let poll_secs = config::load().map(|c| c.poll_interval_secs).unwrap_or(5);
+            let cfg = config::load().unwrap_or_default();
+            let players = make_players(&cfg).await;
+            let filter = profanity::Filter::from_config(&cfg);
             let web_port = parse_web_port(&args);
-            daemon::run(spotify, poll_secs, web_port).await;
+            daemon::run(
+                players,
+                cfg.source_priority,
+                cfg.poll_interval_secs,
+                cfg.lyrics_offset_ms,
+                web_port,
+                filter,
+            )
+            .await;
         }),
         Some("auth") => run_async(async {
             let spotify = make_client();
@@ -131,12 +310,362 @@ commands:
             }
         }),
         Some("config") => config::init(),
+        Some("calibrate") => {
+            let track = arg_value(&args, &["--track"]);
+            let reset = args.iter().any(|a| a == "--reset");
+            run_async(async {
+                let spotify = make_client();
+                auth::authenticate(&spotify).await;
+                or_exit(calibrate::run(&spotify, track, reset).await);
+            });
+        }
+        Some("export") => {
+            let output = arg_value(&args, &["-o", "--output"]);
+            let format = arg_value(&args, &["--format", "-f"])
+                .or_else(|| output.and_then(|o| o.rsplit_once('.')).map(|(_, ext)| ext))
+                .and_then(export::Format::parse)
+                .unwrap_or_else(|| {
+                    eprintln!("usage: lirik export --format srt|vtt|ass|lrc|txt|json [-o file]");
+                    std::process::exit(1);
+                });
+            let offset = arg_value(&args, &["--offset"])
+                .and_then(|s| s.parse().ok())
+                .unwrap_or_else(config_offset);
+            let track = parse_query(&args);
+            run_async(async { or_exit(export::run(format, output, track, offset).await) });
+        }
+        Some("find") => {
+            let mut words = Vec::new();
+            let mut rest = args.iter().skip(2);
+            while let Some(a) = rest.next() {
+                match a.as_str() {
+                    "--limit" | "--pick" => {
+                        rest.next();
+                    }
+                    "--list" => {}
+                    word => words.push(word),
+                }
+            }
+            if words.is_empty() {
+                eprintln!("usage: lirik find \"<lyric snippet>\" [--limit <n>] [--pick <n>] [--list]");
+                std::process::exit(1);
+            }
+            let snippet = words.join(" ");
+            let limit = arg_value(&args, &["--limit"]).and_then(|s| s.parse().ok()).unwrap_or(5);
+            let pick = arg_value(&args, &["--pick"]).and_then(|s| s.parse().ok());
+            let list = args.iter().any(|a| a == "--list");
+            run_async(async {
+                let mut found = or_exit(find::candidates(&snippet).await);
+                found.truncate(limit);
+                if found.is_empty() {
+                    println!("no matches");
+                    return;
+                }
+                find::print(&found);
+                let choice = match pick {
+                    Some(n) => Some(n),
+                    None if list || !std::io::stdin().is_terminal() => None,
+                    None => find::choose(found.len()),
+                };
+                let Some(n) = choice else { return };
+                let spotify = make_client();
+                auth::authenticate(&spotify).await;
+                or_exit(find::play(&spotify, &found, n).await);
+            });
+        }
+        Some("lyrics") => match args.get(2).map(|s| s.as_str()) {
+            Some("get") => {
+                let Some(query) = parse_query(&args) else {
+                    eprintln!("usage: lirik lyrics get --artist <name> --title <name> [--album <name>] [--duration <m:ss>]");
+                    std::process::exit(1);
+                };
+                let format = arg_value(&args, &["--format", "-f"])
+                    .map(|f| {
+                        export::Format::parse(f).unwrap_or_else(|| {
+                            eprintln!("unknown format: {f}");
+                            std::process::exit(1);
+                        })
+                    })
+                    .unwrap_or(export::Format::Txt);
+                let output = arg_value(&args, &["-o", "--output"]);
+                let offset = arg_value(&args, &["--offset"])
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(0);
+                run_async(async {
+                    or_exit(export::run(format, output, Some(query), offset).await)
+                });
+            }
+            Some(cmd @ ("sync-playlist" | "sync-album")) => {
+                let Some(uri) = args.get(3) else {
+                    eprintln!("usage: lirik lyrics {cmd} <uri> [--dir <path>] [--no-cache] [--jobs <n>]");
+                    std::process::exit(1);
+                };
+                let kind = if cmd == "sync-album" { sync::Kind::Album } else { sync::Kind::Playlist };
+                let opts = sync::Options {
+                    dir: arg_value(&args, &["--dir"]).map(config::expand),
+                    cache: !args.iter().any(|a| a == "--no-cache"),
+                    jobs: arg_value(&args, &["--jobs"]).and_then(|s| s.parse().ok()).unwrap_or(4),
+                };
+                if opts.dir.is_none() && !opts.cache {
+                    eprintln!("nothing to do: pass --dir or drop --no-cache");
+                    std::process::exit(1);
+                }
+                run_async(async {
+                    let spotify = make_client();
+                    auth::authenticate(&spotify).await;
+                    or_exit(sync::run(&spotify, kind, uri, opts).await);
+                });
+            }
+            Some("tag") => {
+                let Some(dir) = args.get(3) else {
+                    eprintln!("usage: lirik lyrics tag <dir> [--dry-run] [--skip-existing] [--sylt]");
+                    std::process::exit(1);
+                };
+                let opts = tag::Options {
+                    dry_run: args.iter().any(|a| a == "--dry-run"),
+                    skip_existing: args.iter().any(|a| a == "--skip-existing"),
+                    sylt: args.iter().any(|a| a == "--sylt"),
+                };
+                run_async(async { or_exit(tag::run(&config::expand(dir), opts).await) });
+            }
+            Some("search") => {
+                let Some(phrase) = args.get(3) else {
+                    eprintln!("usage: lirik lyrics search <phrase> [--play [n]] [--json]");
+                    std::process::exit(1);
+                };
+                let hits = or_exit(search::query(phrase));
+                if args.iter().any(|a| a == "--json") {
+                    println!("{}", serde_json::to_string(&hits).unwrap());
+                } else if hits.is_empty() {
+                    println!("no matches");
+                } else {
+                    search::print(&hits);
+                }
+                let play = args.iter().position(|a| a == "--play").map(|i| {
+                    args.get(i + 1).and_then(|s| s.parse().ok()).unwrap_or(1)
+                });
+                if let Some(n) = play {
+                    run_async(async {
+                        let spotify = make_client();
+                        auth::authenticate(&spotify).await;
+                        or_exit(search::play(&spotify, &hits, n).await);
+                    });
+                }
+            }
+            _ => {
+                eprintln!("usage: lirik lyrics get|sync-playlist|sync-album|tag|search ...");
+                std::process::exit(1);
+            }
+        },
+        Some("lrc") => {
+            let (Some("check"), Some(path)) = (args.get(2).map(|s| s.as_str()), args.get(3)) else {
+                eprintln!("usage: lirik lrc check <file> [--duration <m:ss>] [--fix]");
+                std::process::exit(1);
+            };
+            let duration = arg_value(&args, &["--duration"]).and_then(spotify::parse_time);
+            let fix = args.iter().any(|a| a == "--fix");
+            if !or_exit(lrc::run(path, duration, fix)) {
+                std::process::exit(1);
+            }
+        }
+        Some("card") => {
+            let preset = parse_preset(&args);
+            let quote = arg_value(&args, &["--quote"]).and_then(|s| s.parse().ok());
+            let output = arg_value(&args, &["-o", "--output"]).unwrap_or("lirik-card.png");
+            let offset = arg_value(&args, &["--offset"])
+                .and_then(|s| s.parse().ok())
+                .unwrap_or_else(config_offset);
+            or_exit(card::run(preset, quote, output, offset));
+        }
+        Some("loop") => match (args.get(2).map(|s| s.as_str()), args.get(3)) {
+            (None, _) => client::print_loop(),
+            (Some("off" | "clear"), _) => {
+                print_cmd_result(&client::send_command(r#"{"cmd":"loop","arg":"off"}"#))
+            }
+            (Some(from), Some(to)) => {
+                let number = |s: &str| {
+                    s.parse::<usize>().unwrap_or_else(|_| {
+                        eprintln!("not a number: {s}");
+                        std::process::exit(1);
+                    })
+                };
+                let mut arg = format!("{} {}", number(from), number(to));
+                if let Some(n) = arg_value(&args, &["--times", "-n"]) {
+                    arg += &format!(" {}", number(n));
+                }
+                print_cmd_result(&client::send_command(&format!(
+                    r#"{{"cmd":"loop","arg":"{arg}"}}"#
+                )));
+            }
+            _ => {
+                eprintln!("usage: lirik loop <from-line> <to-line> [--times <n>] | lirik loop off");
+                std::process::exit(1);
+            }
+        },
+        Some("queue") => match args.get(2).map(|s| s.as_str()) {
+            None | Some("list") => client::print_queue(),
+            Some("add") if args.len() > 3 => {
+                let item = or_exit(client::query("queue_add", &args[3..].join(" ")));
+                let field = |key: &str| item[key].as_str().unwrap_or_default().to_string();
+                println!("queued {} - {}", field("artist"), field("track"));
+            }
+            _ => {
+                eprintln!("usage: lirik queue | lirik queue add <uri|search>");
+                std::process::exit(1);
+            }
+        },
+        Some("devices") => client::print_devices(),
+        Some("device") => {
+            if args.len() < 3 {
+                eprintln!("usage: lirik device <name|number>  (see lirik devices)");
+                std::process::exit(1);
+            }
+            // names can have spaces, e.g. "Living Room"
+            let cmd = serde_json::json!({"cmd": "device", "arg": args[2..].join(" ")});
+            print_cmd_result(&client::send_command(&cmd.to_string()));
+        }
+        Some("source") => match (args.get(2).map(|s| s.as_str()), args.get(3)) {
+            (None | Some("list"), _) => client::print_sources(),
+            (Some("use"), Some(name)) => print_cmd_result(&client::send_command(
+                &serde_json::json!({"cmd": "source", "arg": name}).to_string(),
+            )),
+            _ => {
+                eprintln!("usage: lirik source list | lirik source use <name|auto>");
+                std::process::exit(1);
+            }
+        },
+        Some("ingest") => {
+            if args.iter().any(|a| a == "--clear") {
+                print_cmd_result(&client::send_command(r#"{"cmd":"clear_now_playing"}"#));
+                return;
+            }
+            // milliseconds, or m:ss
+            let ms = |name| {
+                arg_value(&args, &[name]).map(|s| {
+                    let ms = if s.contains(':') { spotify::parse_time(s) } else { s.parse().ok() };
+                    ms.unwrap_or_else(|| {
+                        eprintln!("bad time for {name}: {s} (use ms or m:ss)");
+                        std::process::exit(1);
+                    })
+                })
+            };
+            let mut update = serde_json::json!({"cmd": "set_now_playing"});
+            let fields = [
+                ("artist", arg_value(&args, &["--artist"]).map(serde_json::Value::from)),
+                ("title", arg_value(&args, &["--title"]).map(serde_json::Value::from)),
+                ("album", arg_value(&args, &["--album"]).map(serde_json::Value::from)),
+                ("album_art", arg_value(&args, &["--art"]).map(serde_json::Value::from)),
+                ("player", arg_value(&args, &["--player"]).map(serde_json::Value::from)),
+                ("duration_ms", ms("--duration").map(serde_json::Value::from)),
+                ("position_ms", ms("--position").map(serde_json::Value::from)),
+            ];
+            for (key, value) in fields {
+                if let Some(value) = value {
+                    update[key] = value;
+                }
+            }
+            if args.iter().any(|a| a == "--playing") {
+                update["playing"] = true.into();
+            } else if args.iter().any(|a| a == "--paused") {
+                update["playing"] = false.into();
+            }
+            print_cmd_result(&client::send_command(&update.to_string()));
+        }
+        Some("video") => {
+            let time = |name| {
+                arg_value(&args, &[name]).map(|s| {
+                    spotify::parse_time(s).unwrap_or_else(|| {
+                        eprintln!("bad time for {name}: {s} (use m:ss)");
+                        std::process::exit(1);
+                    })
+                })
+            };
+            let from = time("--from").unwrap_or(0);
+            let to = time("--to");
+            let output = arg_value(&args, &["-o", "--output"]).unwrap_or("lirik-video.gif");
+            let offset = arg_value(&args, &["--offset"])
+                .and_then(|s| s.parse().ok())
+                .unwrap_or_else(config_offset);
+            or_exit(video::run(from, to, parse_preset(&args), output, offset));
+        }
+        Some("quote") => {
+            let count = |name| arg_value(&args, &[name]).and_then(|s| s.parse().ok()).unwrap_or(0);
+            let offset = arg_value(&args, &["--offset"])
+                .and_then(|s| s.parse().ok())
+                .unwrap_or_else(config_offset);
+            or_exit(quotes::quote(count("--prev"), count("--next"), offset));
+        }
+        Some("quotes") => {
+            let output = arg_value(&args, &["-o", "--output"]);
+            let format = arg_value(&args, &["--format", "-f"])
+                .or_else(|| output.and_then(|o| o.rsplit_once('.')).map(|(_, ext)| ext))
+                .map(|f| {
+                    quotes::Format::parse(f).unwrap_or_else(|| {
+                        eprintln!("unknown format: {f}");
+                        std::process::exit(1);
+                    })
+                })
+                .unwrap_or(quotes::Format::Text);
+            let search = arg_value(&args, &["--search", "-s"]);
+            or_exit(quotes::list(search, format, output));
+        }
         Some("restart") => {
             daemon::kill();
             client::spawn_daemon();
             eprintln!("daemon restarted");
         }
         Some("stop") => daemon::kill(),
+        Some("play") if args.len() > 2 => {
+            let mut words = Vec::new();
+            let mut rest = args[2..].iter();
+            while let Some(a) = rest.next() {
+                match a.as_str() {
+                    "--limit" | "--pick" => {
+                        rest.next();
+                    }
+                    "--album" | "--playlist" | "--list" | "-y" | "--yes" => {}
+                    word => words.push(word),
+                }
+            }
+            let kind = if args.iter().any(|a| a == "--album") {
+                play::Kind::Album
+            } else if args.iter().any(|a| a == "--playlist") {
+                play::Kind::Playlist
+            } else {
+                play::Kind::Track
+            };
+            if words.is_empty() {
+                eprintln!("usage: lirik play \"artist - song\" | --album <search> | --playlist <search> [-y]");
+                std::process::exit(1);
+            }
+            let limit = arg_value(&args, &["--limit"]).and_then(|s| s.parse().ok()).unwrap_or(5);
+            let pick = match args.iter().any(|a| a == "-y" || a == "--yes") {
+                true => Some(1),
+                false => arg_value(&args, &["--pick"]).and_then(|s| s.parse().ok()),
+            };
+            let list = args.iter().any(|a| a == "--list");
+            run_async(async {
+                let spotify = make_client();
+                auth::authenticate(&spotify).await;
+                let found = or_exit(play::search(&spotify, &words.join(" "), kind, limit).await);
+                if found.is_empty() {
+                    println!("no matches");
+                    return;
+                }
+                if pick.is_none() {
+                    play::print(&found);
+                }
+                let choice = match pick {
+                    Some(n) => Some(n),
+                    None if list || !std::io::stdin().is_terminal() => None,
+                    None => find::choose(found.len()),
+                };
+                let Some(n) = choice else { return };
+                let c = n.checked_sub(1).and_then(|i| found.get(i));
+                let c = or_exit(c.ok_or_else(|| format!("no candidate #{n}")));
+                or_exit(play::start(&spotify, c).await);
+            });
+        }
         Some("play" | "toggle") => print_cmd_result(&client::send_command(r#"{"cmd":"toggle"}"#)),
         Some("pause") => print_cmd_result(&client::send_command(r#"{"cmd":"pause"}"#)),
         Some("next") => print_cmd_result(&client::send_command(r#"{"cmd":"next"}"#)),
@@ -154,12 +683,13 @@ commands:
             let plain = has(&args, 'p', "--plain");
             let current = has(&args, 'c', "--current");
             let reverse = has(&args, 'r', "--reverse");
+            let compact = args.iter().any(|a| a == "--compact");
             let offset = parse_offset(&args);
 
             if watch {
                 watch::run(json, offset);
             } else if plain {
-                client::plain(current, reverse, offset);
+                client::plain(current, reverse, compact, offset);
             } else if json {
                 client::json(offset);
             } else {
@@ -0,0 +1,218 @@
+use crate::player::{BoxFuture, Control, PlayerBackend, Repeat};
+use crate::spotify::{fmt_time, DeviceInfo, NowPlaying};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpStream, UnixStream};
+use tokio::sync::Notify;
+
+trait Io: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Io for T {}
+
+/// One connection to MPD, speaking its line protocol: a command per line,
+/// answered by `key: value` lines and `OK`, or a single `ACK` line.
+struct Conn {
+    io: BufReader<Box<dyn Io>>,
+}
+
+// arguments with spaces or quotes have to be quoted
+fn quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn seconds(v: Option<&String>) -> Option<u64> {
+    v?.parse::<f64>().ok().map(|s| (s * 1000.0) as u64)
+}
+
+impl Conn {
+    async fn open(address: &str, password: &str) -> Result<Self, String> {
+        let err = |e: std::io::Error| format!("can't reach mpd at {address}: {e}");
+        let io: Box<dyn Io> = match address {
+            a if a.starts_with('/') || a.starts_with('~') => {
+                Box::new(UnixStream::connect(crate::config::expand(a)).await.map_err(err)?)
+            }
+            a => Box::new(TcpStream::connect(a).await.map_err(err)?),
+        };
+        let mut conn = Self { io: BufReader::new(io) };
+        let greeting = conn.line().await?;
+        if !greeting.starts_with("OK MPD") {
+            return Err(format!("{address} isn't mpd (got {greeting:?})"));
+        }
+        if !password.is_empty() {
+            conn.run(&format!("password {}", quote(password))).await?;
+        }
+        Ok(conn)
+    }
+
+    async fn line(&mut self) -> Result<String, String> {
+        let mut line = String::new();
+        match self.io.read_line(&mut line).await {
+            Ok(0) => Err("mpd closed the connection".into()),
+            Ok(_) => Ok(line.trim_end_matches(['\r', '\n']).to_string()),
+            Err(e) => Err(format!("mpd: {e}")),
+        }
+    }
+
+    /// Sends `command` and collects the reply's fields.
+    async fn run(&mut self, command: &str) -> Result<HashMap<String, String>, String> {
+        let io = self.io.get_mut();
+        io.write_all(format!("{command}\n").as_bytes()).await.map_err(|e| format!("mpd: {e}"))?;
+        let mut fields = HashMap::new();
+        loop {
+            let line = self.line().await?;
+            if line == "OK" {
+                return Ok(fields);
+            }
+            if let Some(err) = line.strip_prefix("ACK ") {
+                return Err(format!("mpd: {err}"));
+            }
+            if let Some((key, value)) = line.split_once(": ") {
+                // the first of repeated keys (e.g. several Artist tags) wins
+                fields.entry(key.to_string()).or_insert_with(|| value.to_string());
+            }
+        }
+    }
+}
+
+/// A Music Player Daemon, over TCP ("host:port") or a unix socket (a path).
+pub struct Mpd {
+    address: String,
+    password: String,
+}
+
+impl Mpd {
+    /// Checks that MPD answers before the daemon starts relying on it.
+    pub async fn connect(address: &str, password: &str) -> Result<Self, String> {
+        Conn::open(address, password).await?;
+        Ok(Self { address: address.to_string(), password: password.to_string() })
+    }
+
+    async fn conn(&self) -> Result<Conn, String> {
+        Conn::open(&self.address, &self.password).await
+    }
+
+    async fn read(&self) -> Option<NowPlaying> {
+        let mut conn = self.conn().await.ok()?;
+        let status = conn.run("status").await.ok()?;
+        let state = status.get("state").map(|s| s.as_str()).unwrap_or("stop");
+        if state == "stop" {
+            return None;
+        }
+        let song = conn.run("currentsong").await.ok()?;
+        let file = song.get("file").cloned().unwrap_or_default();
+        // untagged files fall back to their name, like mpd clients do
+        let track = song.get("Title").cloned().unwrap_or_else(|| {
+            let name = file.rsplit('/').next().unwrap_or_default();
+            name.rsplit_once('.').map_or(name, |(stem, _)| stem).to_string()
+        });
+        if track.is_empty() {
+            return None;
+        }
+        let duration_ms = seconds(status.get("duration"))
+            .or_else(|| seconds(song.get("duration")))
+            .or_else(|| seconds(song.get("Time")))
+            .unwrap_or(0);
+        let elapsed_ms = seconds(status.get("elapsed")).unwrap_or(0);
+        let progress_ms = match duration_ms {
+            0 => elapsed_ms,
+            d => elapsed_ms.min(d),
+        };
+        let flag = |key: &str| status.get(key).is_some_and(|v| v == "1");
+        let repeat = match (flag("repeat"), flag("single")) {
+            (true, true) => "track",
+            (true, false) => "context",
+            _ => "off",
+        };
+        // -1 when mpd has no mixer
+        let volume = status.get("volume").and_then(|v| v.parse::<i32>().ok()).filter(|v| *v >= 0);
+        Some(NowPlaying {
+            id: (!file.is_empty()).then_some(file),
+            artist: song.get("Artist").or_else(|| song.get("AlbumArtist")).cloned().unwrap_or_default(),
+            track,
+            album: song.get("Album").cloned().unwrap_or_default(),
+            album_art: None,
+            popularity: 0,
+            explicit: false,
+            spotify_url: None,
+            progress: fmt_time(progress_ms),
+            progress_ms,
+            duration: fmt_time(duration_ms),
+            duration_ms,
+            is_playing: state == "play",
+            device: Some(DeviceInfo {
+                id: None,
+                name: "mpd".into(),
+                device_type: "mpd".into(),
+                volume: volume.map(|v| v as u32),
+            }),
+            shuffle: flag("random"),
+            repeat: repeat.into(),
+            context: None,
+        })
+    }
+
+    async fn send(&self, control: Control) -> Result<(), String> {
+        let mut conn = self.conn().await?;
+        let command = match control {
+            Control::Play => {
+                // `pause 0` doesn't start a stopped player
+                let status = conn.run("status").await?;
+                match status.get("state").map(|s| s.as_str()) {
+                    Some("pause") => "pause 0".to_string(),
+                    _ => "play".to_string(),
+                }
+            }
+            Control::Pause => "pause 1".into(),
+            Control::Next => "next".into(),
+            Control::Prev => "previous".into(),
+            Control::Volume(v) => format!("setvol {}", v.min(100)),
+            Control::Seek(ms) => format!("seekcur {:.3}", ms as f64 / 1000.0),
+            Control::Shuffle(on) => format!("random {}", on as u8),
+            Control::Repeat(r) => {
+                let (repeat, single) = match r {
+                    Repeat::Off => (0, 0),
+                    Repeat::Context => (1, 0),
+                    Repeat::Track => (1, 1),
+                };
+                format!("command_list_begin\nrepeat {repeat}\nsingle {single}\ncommand_list_end")
+            }
+            // a file or stream URL mpd knows, added to the queue and started
+            Control::PlayUri { uri, position_ms } => {
+                let added = conn.run(&format!("addid {}", quote(&uri))).await?;
+                let id = added.get("Id").ok_or("mpd didn't return the new song's id")?;
+                match position_ms.filter(|ms| *ms > 0) {
+                    Some(ms) => format!("seekid {id} {:.3}", ms as f64 / 1000.0),
+                    None => format!("playid {id}"),
+                }
+            }
+        };
+        conn.run(&command).await.map(|_| ())
+    }
+}
+
+impl PlayerBackend for Mpd {
+    fn now_playing(&self) -> BoxFuture<'_, Option<NowPlaying>> {
+        Box::pin(self.read())
+    }
+
+    fn control(&self, control: Control) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(self.send(control))
+    }
+
+    fn subscribe(&self, wake: Arc<Notify>) {
+        let (address, password) = (self.address.clone(), self.password.clone());
+        tokio::spawn(async move {
+            loop {
+                // `idle` blocks until something changes, so it gets its own
+                // connection; reconnect if mpd restarts
+                if let Ok(mut conn) = Conn::open(&address, &password).await {
+                    while conn.run("idle player mixer options").await.is_ok() {
+                        wake.notify_one();
+                    }
+                }
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+        });
+    }
+}
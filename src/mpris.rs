@@ -0,0 +1,363 @@
+use crate::player::{BoxFuture, Control, PlayerBackend, Repeat};
+use crate::spotify::{fmt_time, DeviceInfo, NowPlaying};
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use zbus::fdo::DBusProxy;
+use zbus::proxy::CacheProperties;
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+use zbus::{Connection, MatchRule, MessageStream, Proxy};
+
+const PREFIX: &str = "org.mpris.MediaPlayer2.";
+const PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER: &str = "org.mpris.MediaPlayer2.Player";
+
+/// Where playback is, kept from the last track change, play/pause or
+/// `Seeked` signal rather than polled.
+#[derive(Default)]
+struct Clock {
+    // unique bus name of the followed player, which signals are sent from
+    owner: String,
+    track: String,
+    base_us: i64,
+    at: Option<Instant>,
+    playing: bool,
+}
+
+impl Clock {
+    fn position_us(&self) -> i64 {
+        match self.at {
+            Some(at) if self.playing => self.base_us + at.elapsed().as_micros() as i64,
+            _ => self.base_us,
+        }
+    }
+
+    fn set(&mut self, position_us: i64) {
+        self.base_us = position_us;
+        self.at = Some(Instant::now());
+    }
+
+    // running past the end of the same track means it started over (repeat
+    // one) without a Seeked
+    fn past_end(&self, duration_ms: u64) -> bool {
+        duration_ms > 0 && self.position_us() / 1000 > duration_ms as i64
+    }
+}
+
+/// Any MPRIS player on the session bus: the Spotify desktop app, mpv,
+/// browsers and most Linux media players.
+pub struct Mpris {
+    conn: Connection,
+    /// Player to follow, e.g. "spotify" or "mpv"; empty picks the one playing.
+    prefer: String,
+    // well-known name of the player currently followed
+    bus: Mutex<Option<String>>,
+    clock: Arc<Mutex<Clock>>,
+}
+
+fn string(v: &Value) -> Option<String> {
+    match v {
+        Value::Str(s) => Some(s.to_string()),
+        Value::ObjectPath(p) => Some(p.to_string()),
+        Value::Value(inner) => string(inner),
+        _ => None,
+    }
+}
+
+fn strings(v: &Value) -> Vec<String> {
+    match v {
+        Value::Array(a) => a.iter().filter_map(string).collect(),
+        Value::Value(inner) => strings(inner),
+        v => string(v).into_iter().collect(),
+    }
+}
+
+fn micros(v: &Value) -> Option<i64> {
+    match v {
+        Value::I64(n) => Some(*n),
+        Value::U64(n) => Some(*n as i64),
+        Value::I32(n) => Some(*n as i64),
+        Value::U32(n) => Some(*n as i64),
+        Value::Value(inner) => micros(inner),
+        _ => None,
+    }
+}
+
+// "/com/spotify/track/<id>" from the desktop app shares offsets with the
+// Web API backend
+fn track_id(trackid: &str) -> String {
+    trackid.strip_prefix("/com/spotify/track/").unwrap_or(trackid).to_string()
+}
+
+impl Mpris {
+    pub async fn connect(prefer: &str) -> Result<Self, String> {
+        let conn = Connection::session()
+            .await
+            .map_err(|e| format!("can't reach the session bus: {e}"))?;
+        Ok(Self {
+            conn,
+            prefer: prefer.to_lowercase(),
+            bus: Mutex::new(None),
+            clock: Arc::new(Mutex::new(Clock::default())),
+        })
+    }
+
+    async fn proxy(&self, bus: &str) -> zbus::Result<Proxy<'static>> {
+        zbus::proxy::Builder::new(&self.conn)
+            .destination(bus.to_string())?
+            .path(PATH)?
+            .interface(PLAYER)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+    }
+
+    // the preferred player if it's there, else one that's playing, else any
+    async fn pick(&self) -> Option<String> {
+        let names = DBusProxy::new(&self.conn).await.ok()?.list_names().await.ok()?;
+        let mut players: Vec<String> = names
+            .iter()
+            .map(|n| n.to_string())
+            .filter(|n| n.starts_with(PREFIX))
+            .collect();
+        players.sort();
+        if !self.prefer.is_empty() {
+            return players
+                .into_iter()
+                .find(|n| n[PREFIX.len()..].to_lowercase().starts_with(&self.prefer));
+        }
+        for bus in &players {
+            let Ok(proxy) = self.proxy(bus).await else { continue };
+            if proxy.get_property::<String>("PlaybackStatus").await.is_ok_and(|s| s == "Playing") {
+                return Some(bus.clone());
+            }
+        }
+        players.into_iter().next()
+    }
+
+    async fn read(&self) -> Option<NowPlaying> {
+        let bus = self.pick().await?;
+        let proxy = self.proxy(&bus).await.ok()?;
+        let meta: HashMap<String, OwnedValue> = proxy.get_property("Metadata").await.ok()?;
+        let status: String = proxy.get_property("PlaybackStatus").await.ok()?;
+        if status == "Stopped" {
+            return None;
+        }
+        let get = |key: &str| meta.get(key).map(|v| &**v);
+        let title = get("xesam:title").and_then(string).unwrap_or_default();
+        if title.is_empty() {
+            return None;
+        }
+        let trackid = get("mpris:trackid").and_then(string).unwrap_or_default();
+        let playing = status == "Playing";
+
+        // a player restarted under the same name has a new owner, and its
+        // Seeked signals come from that
+        let owner = self.owner(&bus).await;
+        let fresh = self.bus.lock().unwrap().as_deref() != Some(bus.as_str())
+            || self.clock.lock().unwrap().owner != owner;
+        let new_track = fresh || self.clock.lock().unwrap().track != trackid;
+        let duration_ms = get("mpris:length").and_then(micros).unwrap_or(0).max(0) as u64 / 1000;
+        let past_end = self.clock.lock().unwrap().past_end(duration_ms);
+        // otherwise Position is only asked for on a new track or player
+        let position = match new_track || past_end {
+            true => proxy.get_property::<i64>("Position").await.ok(),
+            false => None,
+        };
+        let position_us = {
+            let mut c = self.clock.lock().unwrap();
+            c.owner = owner;
+            if new_track {
+                c.track = trackid.clone();
+                c.set(position.unwrap_or(0));
+            } else if let Some(position) = position {
+                c.set(position);
+            } else if c.playing != playing {
+                let now = c.position_us();
+                c.set(now);
+            }
+            c.playing = playing;
+            c.position_us().max(0) as u64
+        };
+        if fresh {
+            *self.bus.lock().unwrap() = Some(bus.clone());
+        }
+
+        let progress_ms = match duration_ms {
+            0 => position_us / 1000,
+            d => (position_us / 1000).min(d),
+        };
+        let shuffle = proxy.get_property::<bool>("Shuffle").await.unwrap_or(false);
+        let repeat = match proxy.get_property::<String>("LoopStatus").await.as_deref() {
+            Ok("Track") => "track",
+            Ok("Playlist") => "context",
+            _ => "off",
+        };
+        let volume = proxy.get_property::<f64>("Volume").await.ok();
+        let name = bus[PREFIX.len()..].split('.').next().unwrap_or_default().to_string();
+        Some(NowPlaying {
+            id: (!trackid.is_empty()).then(|| track_id(&trackid)),
+            artist: get("xesam:artist").map(strings).unwrap_or_default().join(", "),
+            track: title,
+            album: get("xesam:album").and_then(string).unwrap_or_default(),
+            album_art: get("mpris:artUrl").and_then(string),
+            popularity: 0,
+            explicit: false,
+            spotify_url: get("xesam:url").and_then(string).filter(|u| u.contains("spotify")),
+            progress: fmt_time(progress_ms),
+            progress_ms,
+            duration: fmt_time(duration_ms),
+            duration_ms,
+            is_playing: playing,
+            device: Some(DeviceInfo {
+                id: None,
+                name,
+                device_type: "mpris".into(),
+                volume: volume.map(|v| (v * 100.0).round().clamp(0.0, 100.0) as u32),
+            }),
+            shuffle,
+            repeat: repeat.into(),
+            context: None,
+        })
+    }
+
+    async fn owner(&self, bus: &str) -> String {
+        let Ok(dbus) = DBusProxy::new(&self.conn).await else { return String::new() };
+        let Ok(name) = zbus::names::BusName::try_from(bus) else { return String::new() };
+        dbus.get_name_owner(name).await.map(|o| o.to_string()).unwrap_or_default()
+    }
+
+    async fn current(&self) -> Result<(Proxy<'static>, String), String> {
+        let bus = self.bus.lock().unwrap().clone();
+        let bus = match bus {
+            Some(bus) => bus,
+            None => self.pick().await.ok_or("no MPRIS player found")?,
+        };
+        let proxy = self.proxy(&bus).await.map_err(|e| e.to_string())?;
+        Ok((proxy, bus))
+    }
+
+    async fn send(&self, control: Control) -> zbus::Result<()> {
+        let (proxy, _) = self.current().await.map_err(zbus::Error::Failure)?;
+        match control {
+            Control::Play => proxy.call("Play", &()).await,
+            Control::Pause => proxy.call("Pause", &()).await,
+            Control::Next => proxy.call("Next", &()).await,
+            Control::Prev => proxy.call("Previous", &()).await,
+            Control::Volume(v) => Ok(proxy.set_property("Volume", v as f64 / 100.0).await?),
+            Control::Seek(ms) => {
+                let trackid = self.clock.lock().unwrap().track.clone();
+                let path = ObjectPath::try_from(trackid)?;
+                proxy.call("SetPosition", &(path, ms as i64 * 1000)).await
+            }
+            Control::Shuffle(on) => Ok(proxy.set_property("Shuffle", on).await?),
+            Control::Repeat(r) => {
+                let status = match r {
+                    Repeat::Off => "None",
+                    Repeat::Context => "Playlist",
+                    Repeat::Track => "Track",
+                };
+                Ok(proxy.set_property("LoopStatus", status).await?)
+            }
+            Control::PlayUri { uri, position_ms } => {
+                proxy.call::<_, _, ()>("OpenUri", &(uri.as_str(),)).await?;
+                let Some(ms) = position_ms.filter(|ms| *ms > 0) else { return Ok(()) };
+                // SetPosition needs the new track's id, so wait for it to load
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                let meta: HashMap<String, OwnedValue> = proxy.get_property("Metadata").await?;
+                let trackid = meta.get("mpris:trackid").and_then(|v| string(v)).unwrap_or_default();
+                let path = ObjectPath::try_from(trackid)?;
+                proxy.call("SetPosition", &(path, ms as i64 * 1000)).await
+            }
+        }
+    }
+}
+
+impl PlayerBackend for Mpris {
+    fn now_playing(&self) -> BoxFuture<'_, Option<NowPlaying>> {
+        Box::pin(self.read())
+    }
+
+    fn control(&self, control: Control) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(async move { self.send(control).await.map_err(|e| e.to_string()) })
+    }
+
+    fn subscribe(&self, wake: Arc<Notify>) {
+        let conn = self.conn.clone();
+        let clock = self.clock.clone();
+        tokio::spawn(async move {
+            let Ok(rule) = MatchRule::builder()
+                .msg_type(zbus::message::Type::Signal)
+                .path(PATH)
+                .map(|b| b.build())
+            else {
+                return;
+            };
+            let Ok(mut stream) = MessageStream::for_match_rule(rule, &conn, None).await else {
+                return;
+            };
+            while let Some(Ok(msg)) = stream.next().await {
+                let header = msg.header();
+                let member = header.member().map(|m| m.as_str()).unwrap_or_default();
+                match member {
+                    "Seeked" => {
+                        let sender = header.sender().map(|s| s.to_string()).unwrap_or_default();
+                        let Ok(pos) = msg.body().deserialize::<i64>() else { continue };
+                        let mut c = clock.lock().unwrap();
+                        if c.owner != sender {
+                            continue;
+                        }
+                        c.set(pos);
+                    }
+                    // track, play/pause or volume changed; re-read it now
+                    "PropertiesChanged" => {}
+                    _ => continue,
+                }
+                wake.notify_one();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a clock at `base_ms` that was set `ago_ms` ago
+    fn clock(base_ms: i64, ago_ms: u64, playing: bool) -> Clock {
+        Clock {
+            base_us: base_ms * 1000,
+            at: Instant::now().checked_sub(Duration::from_millis(ago_ms)),
+            playing,
+            ..Clock::default()
+        }
+    }
+
+    #[test]
+    fn position_moves_only_while_playing() {
+        let playing = clock(10_000, 2_000, true).position_us() / 1000;
+        assert!((12_000..12_500).contains(&playing), "{playing}");
+        assert_eq!(clock(10_000, 2_000, false).position_us(), 10_000_000);
+    }
+
+    #[test]
+    fn position_without_a_set_is_the_base() {
+        let c = Clock { base_us: 5_000_000, playing: true, ..Clock::default() };
+        assert_eq!(c.position_us(), 5_000_000);
+    }
+
+    #[test]
+    fn past_end_once_running_beyond_the_length() {
+        assert!(!clock(179_000, 500, true).past_end(180_000));
+        assert!(clock(179_000, 1_500, true).past_end(180_000));
+        // paused at the very end isn't past it
+        assert!(!clock(180_000, 5_000, false).past_end(180_000));
+    }
+
+    #[test]
+    fn unknown_length_is_never_past_end() {
+        assert!(!clock(500_000, 1_000, true).past_end(0));
+    }
+}
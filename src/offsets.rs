@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Saved timing corrections, on top of `lyrics_offset_ms`.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Store {
+    // track id -> lyrics source -> ms; different sources carry different timings
+    pub tracks: BTreeMap<String, BTreeMap<String, i64>>,
+    // device name -> ms, mostly negative to make up for speaker latency
+    pub devices: BTreeMap<String, i64>,
+}
+
+pub fn path() -> PathBuf {
+    crate::config::data_dir().join("offsets.json")
+}
+
+pub fn load() -> Store {
+    std::fs::read_to_string(path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+impl Store {
+    pub fn save(&self) -> Result<(), String> {
+        let path = path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(&path, json).map_err(|e| format!("failed to write {}: {e}", path.display()))
+    }
+
+    pub fn track(&self, id: &str, source: &str) -> i64 {
+        self.tracks
+            .get(id)
+            .and_then(|m| m.get(source))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn set_track(&mut self, id: &str, source: &str, ms: i64) {
+        let sources = self.tracks.entry(id.to_string()).or_default();
+        if ms == 0 {
+            sources.remove(source);
+        } else {
+            sources.insert(source.to_string(), ms);
+        }
+        if sources.is_empty() {
+            self.tracks.remove(id);
+        }
+    }
+
+    pub fn device(&self, name: &str) -> i64 {
+        self.devices.get(name).copied().unwrap_or(0)
+    }
+
+    pub fn set_device(&mut self, name: &str, ms: i64) {
+        if ms == 0 {
+            self.devices.remove(name);
+        } else {
+            self.devices.insert(name.to_string(), ms);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::player::{Control, PlayerBackend};
+use crate::spotify::fmt_time;
+use rspotify::model::{SearchResult, SearchType, SimplifiedArtist};
+use rspotify::prelude::*;
+use rspotify::AuthCodeSpotify;
+
+#[derive(Clone, Copy)]
+pub enum Kind {
+    Track,
+    Album,
+    Playlist,
+}
+
+/// A search hit `lirik play` can start.
+pub struct Candidate {
+    pub uri: String,
+    pub name: String,
+    /// Artists, or the playlist's owner.
+    pub by: String,
+    /// Length, release year or track count.
+    pub detail: String,
+}
+
+fn join_artists(artists: &[SimplifiedArtist]) -> String {
+    artists.iter().map(|a| a.name.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+// "artist - song" searches with field filters, so the artist isn't taken
+// for part of the title
+fn track_query(query: &str) -> String {
+    match query.split_once(" - ") {
+        Some((artist, title)) => format!("track:{} artist:{}", title.trim(), artist.trim()),
+        None => query.to_string(),
+    }
+}
+
+async fn search_once(
+    spotify: &AuthCodeSpotify,
+    query: &str,
+    kind: Kind,
+    limit: u32,
+) -> Result<Vec<Candidate>, String> {
+    let search_type = match kind {
+        Kind::Track => SearchType::Track,
+        Kind::Album => SearchType::Album,
+        Kind::Playlist => SearchType::Playlist,
+    };
+    let result = spotify
+        .search(query, search_type, None, None, Some(limit), None)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(match result {
+        SearchResult::Tracks(page) => page
+            .items
+            .into_iter()
+            .filter_map(|t| {
+                Some(Candidate {
+                    uri: t.id.as_ref()?.uri(),
+                    by: join_artists(&t.artists),
+                    detail: fmt_time(t.duration.num_milliseconds() as u64),
+                    name: t.name,
+                })
+            })
+            .collect(),
+        SearchResult::Albums(page) => page
+            .items
+            .into_iter()
+            .filter_map(|a| {
+                Some(Candidate {
+                    uri: a.id.as_ref()?.uri(),
+                    by: join_artists(&a.artists),
+                    detail: a.release_date.as_deref().unwrap_or("").chars().take(4).collect(),
+                    name: a.name,
+                })
+            })
+            .collect(),
+        SearchResult::Playlists(page) => page
+            .items
+            .into_iter()
+            .map(|p| Candidate {
+                uri: p.id.uri(),
+                by: p.owner.display_name.unwrap_or(p.owner.id.id().to_string()),
+                detail: format!("{} tracks", p.tracks.total),
+                name: p.name,
+            })
+            .collect(),
+        _ => Vec::new(),
+    })
+}
+
+/// Searches Spotify, best match first.
+pub async fn search(
+    spotify: &AuthCodeSpotify,
+    query: &str,
+    kind: Kind,
+    limit: u32,
+) -> Result<Vec<Candidate>, String> {
+    if let Kind::Track = kind {
+        let found = search_once(spotify, &track_query(query), kind, limit).await?;
+        // a loose query for titles with odd punctuation
+        if !found.is_empty() || !query.contains(" - ") {
+            return Ok(found);
+        }
+        return search_once(spotify, &query.replace(" - ", " "), kind, limit).await;
+    }
+    search_once(spotify, query, kind, limit).await
+}
+
+pub fn print(found: &[Candidate]) {
+    let width = found.len().to_string().len();
+    for (i, c) in found.iter().enumerate() {
+        let n = i + 1;
+        let detail = match c.detail.is_empty() {
+            true => String::new(),
+            false => format!(" ({})", c.detail),
+        };
+        println!("{n:>width$}. {} - {}{detail}", c.by, c.name);
+    }
+}
+
+/// Starts a track, album or playlist URI (or bare track ID) on the active
+/// device. This goes straight to Spotify rather than through the daemon,
+/// which may be showing a player that can't take a Spotify URI.
+pub async fn play_uri(
+    spotify: &AuthCodeSpotify,
+    uri: &str,
+    position_ms: Option<u64>,
+) -> Result<(), String> {
+    let control = Control::PlayUri { uri: uri.to_string(), position_ms };
+    spotify.control(control).await.map_err(|e| match e.contains("404") {
+        true => format!("{e} (no active device: open Spotify somewhere first)"),
+        false => e,
+    })
+}
+
+/// Starts `c` on the active device.
+pub async fn start(spotify: &AuthCodeSpotify, c: &Candidate) -> Result<(), String> {
+    play_uri(spotify, &c.uri, None).await?;
+    println!("playing {} - {}", c.by, c.name);
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use crate::spotify::{DeviceInfo, NowPlaying};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+// boxed so backends can be picked at runtime and held as `dyn PlayerBackend`
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+#[derive(Clone, Copy)]
+pub enum Repeat {
+    Off,
+    Context,
+    Track,
+}
+
+/// A playback command, already resolved against the current state (e.g.
+/// `toggle` arrives as `Play` or `Pause`).
+#[derive(Clone)]
+pub enum Control {
+    Play,
+    Pause,
+    Next,
+    Prev,
+    Volume(u8),
+    Seek(u64),
+    Shuffle(bool),
+    Repeat(Repeat),
+    /// Starts a track by URI or ID, optionally at a position, or an album
+    /// or playlist by URI.
+    PlayUri { uri: String, position_ms: Option<u64> },
+}
+
+/// A track coming up in the player's queue.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct QueueItem {
+    pub uri: Option<String>,
+    pub artist: String,
+    pub track: String,
+    pub duration_ms: u64,
+}
+
+/// A device playback can be moved to.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Device {
+    #[serde(flatten)]
+    pub info: DeviceInfo,
+    /// Whether it's the one playing now.
+    pub active: bool,
+}
+
+/// Where the daemon gets playback state from and sends controls to.
+pub trait PlayerBackend: Send + Sync {
+    /// What's playing now; `None` when nothing is.
+    fn now_playing(&self) -> BoxFuture<'_, Option<NowPlaying>>;
+
+    fn control(&self, control: Control) -> BoxFuture<'_, Result<(), String>>;
+
+    /// Whether `queue`, `enqueue` and `skip_queued` work, so they can go to
+    /// a player that has a queue when the one shown doesn't.
+    fn has_queue(&self) -> bool {
+        false
+    }
+
+    /// Tracks coming up after the current one, in play order.
+    fn queue(&self) -> BoxFuture<'_, Result<Vec<QueueItem>, String>> {
+        Box::pin(async { Err("this player has no queue".to_string()) })
+    }
+
+    /// Appends a track to the queue, by URI or as the best match for a
+    /// search, and returns it.
+    fn enqueue<'a>(&'a self, _query: &'a str) -> BoxFuture<'a, Result<QueueItem, String>> {
+        Box::pin(async { Err("this player has no queue".to_string()) })
+    }
+
+    /// Plays the `n`th track of `queue` (1-based).
+    fn skip_queued(&self, _n: usize) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(async { Err("this player has no queue".to_string()) })
+    }
+
+    /// Whether `devices` and `transfer` work, so they can go to a player
+    /// that has devices when the one shown doesn't.
+    fn has_devices(&self) -> bool {
+        false
+    }
+
+    /// Devices playback can move between, e.g. Spotify Connect speakers.
+    fn devices(&self) -> BoxFuture<'_, Result<Vec<Device>, String>> {
+        Box::pin(async { Err("this player has no devices to switch between".to_string()) })
+    }
+
+    /// Moves playback to the device with this ID, keeping it playing or paused.
+    fn transfer<'a>(&'a self, _device_id: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async { Err("this player has no devices to switch between".to_string()) })
+    }
+
+    /// Hands over the poll loop's wake-up, for backends that are told about
+    /// changes (seeks, track changes) instead of having to poll for them.
+    fn subscribe(&self, _wake: Arc<Notify>) {}
+}
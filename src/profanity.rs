@@ -0,0 +1,112 @@
+use crate::config::{Config, MaskMode};
+use crate::lyrics::Lyrics;
+use std::collections::HashSet;
+
+// entries ending in `*` match any word starting with the stem
+const EN: &[&str] = &[
+    "fuck*", "motherfuck*", "shit*", "bullshit", "bitch*", "cunt*", "dick", "dicks", "cock",
+    "cocks", "pussy", "pussies", "asshole*", "ass", "bastard*", "nigga*", "nigger*", "whore*",
+    "slut*", "damn", "goddamn",
+];
+const ES: &[&str] = &[
+    "puta*", "puto*", "mierda", "coño", "joder", "cabrón", "cabron", "pendej*", "verga",
+    "chinga*", "culo",
+];
+const PT: &[&str] = &["porra", "caralho", "merda", "puta*", "foder", "foda*", "buceta"];
+const FR: &[&str] = &[
+    "putain", "merde", "connard*", "connasse", "salope*", "encul*", "nique", "niquer",
+];
+const DE: &[&str] = &[
+    "scheiße", "scheisse", "scheiß*", "fick*", "arschloch*", "hure*", "fotze", "wichser",
+];
+const IT: &[&str] = &["cazzo", "merda", "stronz*", "vaffanculo", "puttana*", "minchia"];
+const TR: &[&str] = &[
+    "siktir*", "sik", "sikerim", "amk", "orospu*", "piç", "göt", "yarrak*", "yarak", "amına",
+];
+
+fn builtin(lang: &str) -> &'static [&'static str] {
+    match lang {
+        "en" => EN,
+        "es" => ES,
+        "pt" => PT,
+        "fr" => FR,
+        "de" => DE,
+        "it" => IT,
+        "tr" => TR,
+        _ => &[],
+    }
+}
+
+pub struct Filter {
+    mode: MaskMode,
+    words: HashSet<String>,
+    stems: Vec<String>,
+}
+
+impl Filter {
+    pub fn from_config(cfg: &Config) -> Self {
+        let mut words = HashSet::new();
+        let mut stems = Vec::new();
+        let builtins = cfg.profanity_languages.iter().flat_map(|l| builtin(l));
+        for entry in builtins.copied().chain(cfg.profanity_words.iter().map(|w| w.as_str())) {
+            let entry = entry.trim().to_lowercase();
+            match entry.strip_suffix('*') {
+                Some(stem) if !stem.is_empty() => stems.push(stem.to_string()),
+                Some(_) => {}
+                None if !entry.is_empty() => {
+                    words.insert(entry);
+                }
+                None => {}
+            }
+        }
+        Self { mode: cfg.mask_profanity, words, stems }
+    }
+
+    pub fn applies(&self, explicit: bool) -> bool {
+        match self.mode {
+            MaskMode::Off => false,
+            MaskMode::On => true,
+            MaskMode::Explicit => explicit,
+        }
+    }
+
+    fn matches(&self, word: &str) -> bool {
+        let word = word.to_lowercase();
+        self.words.contains(&word) || self.stems.iter().any(|s| word.starts_with(s.as_str()))
+    }
+
+    fn push_word(&self, word: &mut String, out: &mut String) {
+        if self.matches(word) {
+            let mut chars = word.chars();
+            if let Some(first) = chars.next() {
+                out.push(first);
+                out.extend(chars.map(|_| '*'));
+            }
+        } else {
+            out.push_str(word);
+        }
+        word.clear();
+    }
+
+    /// Masks every listed word in `text`, keeping its first letter: "shit" -> "s***".
+    pub fn mask_line(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut word = String::new();
+        for c in text.chars() {
+            if c.is_alphanumeric() {
+                word.push(c);
+            } else {
+                self.push_word(&mut word, &mut out);
+                out.push(c);
+            }
+        }
+        self.push_word(&mut word, &mut out);
+        out
+    }
+
+    pub fn mask(&self, lyrics: &mut Lyrics) {
+        for line in &mut lyrics.lines {
+            line.text = self.mask_line(&line.text);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use crate::client;
+use crate::search::normalize;
+use crate::spotify::{fmt_time, State};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Quote {
+    pub artist: String,
+    pub track: String,
+    pub spotify_url: Option<String>,
+    #[serde(default)]
+    pub album_art: Option<String>,
+    pub time_ms: u64,
+    pub lines: Vec<String>,
+    /// RFC 3339, local time.
+    pub date: String,
+}
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Text,
+    Markdown,
+    Json,
+}
+
+impl Format {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "txt" | "text" => Some(Self::Text),
+            "md" | "markdown" => Some(Self::Markdown),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+// one JSON object per line, so saving never rewrites the journal
+pub fn path() -> PathBuf {
+    crate::config::data_dir().join("quotes.jsonl")
+}
+
+pub fn load() -> Vec<Quote> {
+    let Ok(contents) = std::fs::read_to_string(path()) else { return Vec::new() };
+    contents.lines().filter_map(|l| serde_json::from_str(l).ok()).collect()
+}
+
+fn append(quote: &Quote) -> Result<(), String> {
+    let path = path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("failed to open {}: {e}", path.display()))?;
+    let json = serde_json::to_string(quote).map_err(|e| e.to_string())?;
+    writeln!(file, "{json}").map_err(|e| e.to_string())
+}
+
+/// Saves lines `from..=to` of the playing track's lyrics to the journal.
+pub fn save(state: &State, from: usize, to: usize) -> Result<Quote, String> {
+    let np = state.now_playing.as_ref().ok_or("nothing playing right now")?;
+    let ly = state.lyrics.as_ref().ok_or("no lyrics found")?;
+    let to = to.min(ly.lines.len().saturating_sub(1));
+    let picked: Vec<_> = ly
+        .lines
+        .get(from..=to)
+        .unwrap_or_default()
+        .iter()
+        .filter(|l| !l.text.trim().is_empty())
+        .collect();
+    let first = picked.first().ok_or("nothing to quote here")?;
+    let quote = Quote {
+        artist: np.artist.clone(),
+        track: np.track.clone(),
+        spotify_url: np.spotify_url.clone(),
+        album_art: np.album_art.clone(),
+        time_ms: first.time_ms,
+        lines: picked.iter().map(|l| l.text.clone()).collect(),
+        date: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+    };
+    append(&quote)?;
+    Ok(quote)
+}
+
+/// `lirik quote`: saves the current line plus `prev` lines before and `next` after.
+pub fn quote(prev: usize, next: usize, offset_ms: i64) -> Result<(), String> {
+    let state = client::fetch_state();
+    let np = state.estimate().ok_or("nothing playing right now")?;
+    let idx = client::current_index(&state, &np, offset_ms)
+        .ok_or("no current line (lyrics missing, unsynced or not started yet)")?;
+    let quote = save(&state, idx.saturating_sub(prev), idx + next)?;
+    println!("saved: {}", quote.lines.join(" / "));
+    Ok(())
+}
+
+fn day(date: &str) -> &str {
+    date.get(..10).unwrap_or(date)
+}
+
+fn render(quotes: &[&Quote], format: Format) -> String {
+    let mut out = String::new();
+    match format {
+        Format::Json => {
+            out = serde_json::to_string_pretty(quotes).unwrap_or_default();
+            out.push('\n');
+        }
+        Format::Text => {
+            for (i, q) in quotes.iter().enumerate() {
+                let (date, time) = (day(&q.date), fmt_time(q.time_ms));
+                out += &format!("{:>3}. {date}  {} - {} [{time}]\n", i + 1, q.artist, q.track);
+                for line in &q.lines {
+                    out += &format!("     {line}\n");
+                }
+            }
+        }
+        Format::Markdown => {
+            for q in quotes {
+                for line in &q.lines {
+                    out += &format!("> {line}\n");
+                }
+                let time = fmt_time(q.time_ms);
+                let time = match &q.spotify_url {
+                    Some(url) => format!("[{time}]({url})"),
+                    None => time,
+                };
+                out += &format!("\n— {}, *{}* ({time}), {}\n\n", q.artist, q.track, day(&q.date));
+            }
+        }
+    }
+    out
+}
+
+/// `lirik quotes`: lists the journal, optionally filtered, to stdout or a file.
+pub fn list(search: Option<&str>, format: Format, output: Option<&str>) -> Result<(), String> {
+    let all = load();
+    let want = search.map(normalize).unwrap_or_default();
+    let shown: Vec<&Quote> = all
+        .iter()
+        .filter(|q| {
+            let hay = normalize(&format!("{} {} {}", q.artist, q.track, q.lines.join(" ")));
+            want.is_empty() || format!(" {hay} ").contains(&format!(" {want} "))
+        })
+        .collect();
+    if shown.is_empty() && output.is_none() && !matches!(format, Format::Json) {
+        println!("no quotes");
+        return Ok(());
+    }
+    let out = render(&shown, format);
+    match output {
+        Some(path) => {
+            std::fs::write(path, out).map_err(|e| format!("failed to write {path}: {e}"))?;
+            eprintln!("wrote {} quotes to {path}", shown.len());
+        }
+        None => print!("{out}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,241 @@
+use crate::lyrics::{self, lrc_timestamp, normalize_name, Lyrics};
+use crate::{cache, client, play, spotify};
+use rspotify::AuthCodeSpotify;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::sync::RwLock;
+
+#[derive(Serialize, Deserialize)]
+pub struct Hit {
+    pub artist: String,
+    pub title: String,
+    pub source: String,
+    /// `None` for unsynced lyrics.
+    pub time_ms: Option<u64>,
+    pub text: String,
+}
+
+struct Doc {
+    artist: String,
+    title: String,
+    source: String,
+}
+
+struct Line {
+    doc: usize,
+    time_ms: Option<u64>,
+    text: String,
+    norm: String,
+}
+
+// file count and newest mtime across the lyrics store; a change means rebuild
+type Stamp = (usize, Option<SystemTime>);
+
+/// Word index over every line in the overrides, `lyrics_dirs` and the cache.
+#[derive(Default)]
+pub struct Index {
+    docs: Vec<Doc>,
+    lines: Vec<Line>,
+    words: HashMap<String, Vec<usize>>,
+    seen: HashSet<String>,
+    stamp: Stamp,
+}
+
+/// Lowercases and turns punctuation into single spaces, so "Don't-stop!" and
+/// "don t stop" match.
+pub fn normalize(s: &str) -> String {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn dirs() -> Vec<(PathBuf, &'static str)> {
+    let mut dirs = vec![(lyrics::overrides_dir(), "override")];
+    let local = crate::config::load().map(|c| c.lyrics_dirs).unwrap_or_default();
+    dirs.extend(local.iter().map(|d| (crate::config::expand(d), "local")));
+    dirs.push((cache::dir(), "cache"));
+    dirs
+}
+
+fn files(dir: &Path, source: &str) -> Vec<PathBuf> {
+    let ext = if source == "cache" { "json" } else { "lrc" };
+    let Ok(entries) = std::fs::read_dir(dir) else { return Vec::new() };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e.eq_ignore_ascii_case(ext)))
+        .collect();
+    files.sort();
+    files
+}
+
+fn stamp() -> Stamp {
+    let mut count = 0;
+    let mut newest = None;
+    for (dir, source) in dirs() {
+        for path in files(&dir, source) {
+            count += 1;
+            let modified = path.metadata().and_then(|m| m.modified()).ok();
+            newest = newest.max(modified);
+        }
+    }
+    (count, newest)
+}
+
+fn id_tag<'a>(contents: &'a str, key: &str) -> Option<&'a str> {
+    let prefix = format!("[{key}:");
+    contents.lines().find_map(|l| {
+        let rest = l.trim().strip_prefix(prefix.as_str())?;
+        Some(rest.strip_suffix(']')?.trim())
+    })
+}
+
+// [ar:]/[ti:] tags when present, else "Artist - Title.lrc"
+fn lrc_names(path: &Path, contents: &str) -> (String, String) {
+    let stem = path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+    let (artist, title) = stem.split_once(" - ").unwrap_or(("", &stem));
+    (
+        id_tag(contents, "ar").unwrap_or(artist).to_string(),
+        id_tag(contents, "ti").unwrap_or(title).to_string(),
+    )
+}
+
+impl Index {
+    pub fn build() -> Self {
+        let mut index = Self { stamp: stamp(), ..Self::default() };
+        // same order as the provider chain, so the copy you actually see wins
+        for (dir, source) in dirs() {
+            for path in files(&dir, source) {
+                let Ok(contents) = std::fs::read_to_string(&path) else { continue };
+                if source == "cache" {
+                    if let Ok(entry) = serde_json::from_str::<cache::Entry>(&contents) {
+                        index.add(&entry.artist, &entry.title, &entry.lyrics);
+                    }
+                } else {
+                    let (artist, title) = lrc_names(&path, &contents);
+                    index.add(&artist, &title, &lyrics::from_file(&contents, source));
+                }
+            }
+        }
+        index
+    }
+
+    fn add(&mut self, artist: &str, title: &str, ly: &Lyrics) {
+        if !self.seen.insert(normalize_name(&format!("{artist}{title}"))) {
+            return;
+        }
+        let doc = self.docs.len();
+        self.docs.push(Doc {
+            artist: artist.to_string(),
+            title: title.to_string(),
+            source: ly.source.clone(),
+        });
+        for l in &ly.lines {
+            let norm = normalize(&l.text);
+            if norm.is_empty() {
+                continue;
+            }
+            let id = self.lines.len();
+            let words: HashSet<&str> = norm.split(' ').collect();
+            for word in words {
+                self.words.entry(word.to_string()).or_default().push(id);
+            }
+            self.lines.push(Line {
+                doc,
+                time_ms: ly.synced.then_some(l.time_ms),
+                text: l.text.clone(),
+                norm,
+            });
+        }
+    }
+
+    /// Lines containing `phrase` as whole words, first match per song.
+    pub fn search(&self, phrase: &str, limit: usize) -> Vec<Hit> {
+        let want = normalize(phrase);
+        if want.is_empty() {
+            return Vec::new();
+        }
+        // only lines that have every word can match; scan the rarest word's list
+        let mut rarest: Option<&Vec<usize>> = None;
+        for word in want.split(' ') {
+            let Some(ids) = self.words.get(word) else { return Vec::new() };
+            if rarest.is_none_or(|r| ids.len() < r.len()) {
+                rarest = Some(ids);
+            }
+        }
+        let padded = format!(" {want} ");
+        let mut docs = HashSet::new();
+        let mut hits = Vec::new();
+        for &id in rarest.into_iter().flatten() {
+            let line = &self.lines[id];
+            if !format!(" {} ", line.norm).contains(&padded) || !docs.insert(line.doc) {
+                continue;
+            }
+            let doc = &self.docs[line.doc];
+            hits.push(Hit {
+                artist: doc.artist.clone(),
+                title: doc.title.clone(),
+                source: doc.source.clone(),
+                time_ms: line.time_ms,
+                text: line.text.clone(),
+            });
+            if hits.len() == limit {
+                break;
+            }
+        }
+        hits
+    }
+}
+
+/// Rebuilds the daemon's index if anything in the lyrics store changed.
+pub async fn refresh(index: &RwLock<Option<Index>>) {
+    let Ok(current) = tokio::task::spawn_blocking(stamp).await else { return };
+    if index.read().await.as_ref().is_some_and(|i| i.stamp == current) {
+        return;
+    }
+    if let Ok(built) = tokio::task::spawn_blocking(Index::build).await {
+        *index.write().await = Some(built);
+    }
+}
+
+/// Asks the daemon for lines matching `phrase`.
+pub fn query(phrase: &str) -> Result<Vec<Hit>, String> {
+    let data = client::query("search", phrase)?;
+    serde_json::from_value(data).map_err(|e| format!("bad search response: {e}"))
+}
+
+pub fn print(hits: &[Hit]) {
+    for (i, hit) in hits.iter().enumerate() {
+        let ts = hit.time_ms.map_or("[--:--.--]".into(), lrc_timestamp);
+        println!("{:>3}. {} - {} ({})", i + 1, hit.artist, hit.title, hit.source);
+        println!("     {ts} {}", hit.text);
+    }
+}
+
+/// Starts hit number `n` (1-based) on Spotify at the matched line.
+pub async fn play(spotify: &AuthCodeSpotify, hits: &[Hit], n: usize) -> Result<(), String> {
+    let hit = n
+        .checked_sub(1)
+        .and_then(|i| hits.get(i))
+        .ok_or_else(|| format!("no result #{n}"))?;
+    play_at(spotify, &hit.artist, &hit.title, hit.time_ms.unwrap_or(0)).await
+}
+
+/// Looks a track up on Spotify and starts it there at `pos_ms`.
+pub async fn play_at(
+    spotify: &AuthCodeSpotify,
+    artist: &str,
+    title: &str,
+    pos_ms: u64,
+) -> Result<(), String> {
+    let id = spotify::find_track(spotify, artist, title)
+        .await?
+        .ok_or_else(|| format!("{artist} - {title} not found on Spotify"))?;
+    play::play_uri(spotify, &id, Some(pos_ms)).await?;
+    println!("playing {artist} - {title} from {}", spotify::fmt_time(pos_ms));
+    Ok(())
+}
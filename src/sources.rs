@@ -0,0 +1,292 @@
+use crate::config::Priority;
+use crate::player::{BoxFuture, Control, Device, PlayerBackend, QueueItem};
+use crate::spotify::NowPlaying;
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::Notify;
+
+pub struct Source {
+    pub name: String,
+    pub player: Arc<dyn PlayerBackend>,
+}
+
+// what a source had at the last poll
+#[derive(Default)]
+struct Seen {
+    np: Option<NowPlaying>,
+    track: String,
+    playing: bool,
+    // when it last went from paused, stopped or another track to playing this one
+    started: Option<Instant>,
+}
+
+/// A source as `lirik source list` shows it.
+#[derive(Serialize, Deserialize)]
+pub struct Status {
+    pub name: String,
+    pub playing: bool,
+    /// "artist - title", when it has a track.
+    pub track: Option<String>,
+    pub shown: bool,
+    pub pinned: bool,
+}
+
+/// Every player the daemon follows at once, shown one at a time: the
+/// pinned one, else whichever `priority` picks.
+pub struct Sources {
+    sources: Vec<Source>,
+    priority: Priority,
+    seen: Mutex<Vec<Seen>>,
+    shown: Mutex<Option<usize>>,
+    pinned: Mutex<Option<usize>>,
+}
+
+impl Sources {
+    pub fn new(sources: Vec<Source>, priority: Priority) -> Self {
+        let seen = sources.iter().map(|_| Seen::default()).collect();
+        Self {
+            sources,
+            priority,
+            seen: Mutex::new(seen),
+            shown: Mutex::new(None),
+            pinned: Mutex::new(None),
+        }
+    }
+
+    /// Polls every source and returns the one to show, by name.
+    pub async fn poll(&self) -> Option<(String, NowPlaying)> {
+        let polled = join_all(self.sources.iter().map(|s| s.player.now_playing())).await;
+        let pinned = *self.pinned.lock().unwrap();
+        let shown = *self.shown.lock().unwrap();
+        let mut seen = self.seen.lock().unwrap();
+        for (seen, np) in seen.iter_mut().zip(polled) {
+            let track = np.as_ref().map(|n| format!("{}\0{}", n.artist, n.track)).unwrap_or_default();
+            let playing = np.as_ref().is_some_and(|n| n.is_playing);
+            if np.is_none() {
+                seen.started = None;
+            } else if playing && (!seen.playing || track != seen.track) {
+                seen.started = Some(Instant::now());
+            }
+            *seen = Seen { np, track, playing, started: seen.started };
+        }
+        let pick = match pinned {
+            Some(i) => Some(i),
+            None => self.pick(&seen, shown),
+        };
+        let pick = pick.filter(|&i| seen[i].np.is_some());
+        *self.shown.lock().unwrap() = pick;
+        let i = pick?;
+        Some((self.sources[i].name.clone(), seen[i].np.clone()?))
+    }
+
+    fn pick(&self, seen: &[Seen], shown: Option<usize>) -> Option<usize> {
+        let has = |i: &usize| seen[*i].np.is_some();
+        let mut all = 0..seen.len();
+        match self.priority {
+            Priority::Order => all.find(has),
+            // a paused track stays up until something else plays
+            Priority::Playing => all.find(|&i| seen[i].playing).or(shown),
+            // playing beats paused, then the latest start; ties go to the
+            // source listed first
+            Priority::Recent => all
+                .filter(has)
+                .max_by_key(|&i| (seen[i].playing, seen[i].started, Reverse(i))),
+        }
+    }
+
+    /// Shows only `name` from now on; "auto" goes back to `priority`.
+    pub fn pin(&self, name: &str) -> Result<(), String> {
+        let pinned = match name {
+            "auto" => None,
+            name => Some(self.sources.iter().position(|s| s.name == name).ok_or_else(|| {
+                let names: Vec<&str> = self.sources.iter().map(|s| s.name.as_str()).collect();
+                format!("no source named {name} (have {}, or auto)", names.join(", "))
+            })?),
+        };
+        *self.pinned.lock().unwrap() = pinned;
+        Ok(())
+    }
+
+    // the source on screen, else the pinned one
+    fn target(&self) -> Option<&Source> {
+        let i = self.shown.lock().unwrap().or(*self.pinned.lock().unwrap())?;
+        self.sources.get(i)
+    }
+
+    // sends `call` to the target if it `can` take it; else (e.g. `play` after
+    // startup, or the queue while an MPRIS player is shown) to each source
+    // that can in turn until one takes it
+    async fn dispatch<'a, T>(
+        &'a self,
+        nobody: &str,
+        can: impl Fn(&dyn PlayerBackend) -> bool,
+        call: impl Fn(&'a dyn PlayerBackend) -> BoxFuture<'a, Result<T, String>>,
+    ) -> Result<T, String> {
+        if let Some(source) = self.target()
+            && can(&*source.player)
+        {
+            return call(&*source.player).await;
+        }
+        let mut err = nobody.to_string();
+        for source in self.sources.iter().filter(|s| can(&*s.player)) {
+            match call(&*source.player).await {
+                Ok(v) => return Ok(v),
+                Err(e) => err = e,
+            }
+        }
+        Err(err)
+    }
+
+    pub fn list(&self) -> Vec<Status> {
+        let shown = *self.shown.lock().unwrap();
+        let pinned = *self.pinned.lock().unwrap();
+        let seen = self.seen.lock().unwrap();
+        self.sources
+            .iter()
+            .zip(seen.iter())
+            .enumerate()
+            .map(|(i, (source, seen))| Status {
+                name: source.name.clone(),
+                playing: seen.playing,
+                track: seen.np.as_ref().map(|n| format!("{} - {}", n.artist, n.track)),
+                shown: shown == Some(i),
+                pinned: pinned == Some(i),
+            })
+            .collect()
+    }
+}
+
+impl PlayerBackend for Sources {
+    fn now_playing(&self) -> BoxFuture<'_, Option<NowPlaying>> {
+        Box::pin(async move { self.poll().await.map(|(_, np)| np) })
+    }
+
+    fn control(&self, control: Control) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(self.dispatch("no player to control", |_| true, move |p| p.control(control.clone())))
+    }
+
+    fn has_queue(&self) -> bool {
+        self.sources.iter().any(|s| s.player.has_queue())
+    }
+
+    fn queue(&self) -> BoxFuture<'_, Result<Vec<QueueItem>, String>> {
+        Box::pin(self.dispatch("no player with a queue", |p| p.has_queue(), |p| p.queue()))
+    }
+
+    fn enqueue<'a>(&'a self, query: &'a str) -> BoxFuture<'a, Result<QueueItem, String>> {
+        let can = |p: &dyn PlayerBackend| p.has_queue();
+        Box::pin(self.dispatch("no player with a queue", can, move |p| p.enqueue(query)))
+    }
+
+    fn skip_queued(&self, n: usize) -> BoxFuture<'_, Result<(), String>> {
+        let can = |p: &dyn PlayerBackend| p.has_queue();
+        Box::pin(self.dispatch("no player with a queue", can, move |p| p.skip_queued(n)))
+    }
+
+    fn has_devices(&self) -> bool {
+        self.sources.iter().any(|s| s.player.has_devices())
+    }
+
+    fn devices(&self) -> BoxFuture<'_, Result<Vec<Device>, String>> {
+        Box::pin(self.dispatch("no player with devices", |p| p.has_devices(), |p| p.devices()))
+    }
+
+    fn transfer<'a>(&'a self, device_id: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        let can = |p: &dyn PlayerBackend| p.has_devices();
+        Box::pin(self.dispatch("no player with devices", can, move |p| p.transfer(device_id)))
+    }
+
+    fn subscribe(&self, wake: Arc<Notify>) {
+        for source in &self.sources {
+            source.player.subscribe(wake.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn np(playing: bool) -> NowPlaying {
+        NowPlaying {
+            id: None,
+            artist: "a".into(),
+            track: "t".into(),
+            album: String::new(),
+            album_art: None,
+            popularity: 0,
+            explicit: false,
+            spotify_url: None,
+            progress_ms: 0,
+            progress: String::new(),
+            duration_ms: 0,
+            duration: String::new(),
+            is_playing: playing,
+            device: None,
+            shuffle: false,
+            repeat: String::new(),
+            context: None,
+        }
+    }
+
+    // a source with a track, playing or paused, that started `ago_secs` ago
+    fn seen(playing: bool, ago_secs: Option<u64>) -> Seen {
+        Seen {
+            np: Some(np(playing)),
+            track: "a\0t".into(),
+            playing,
+            started: ago_secs.and_then(|s| Instant::now().checked_sub(Duration::from_secs(s))),
+        }
+    }
+
+    fn pick(priority: Priority, seen: &[Seen], shown: Option<usize>) -> Option<usize> {
+        Sources::new(Vec::new(), priority).pick(seen, shown)
+    }
+
+    #[test]
+    fn recent_prefers_the_latest_start() {
+        let all = [seen(true, Some(60)), seen(true, Some(5)), seen(true, Some(30))];
+        assert_eq!(pick(Priority::Recent, &all, None), Some(1));
+    }
+
+    #[test]
+    fn recent_prefers_playing_over_a_later_pause() {
+        let all = [seen(true, Some(60)), seen(false, Some(5))];
+        assert_eq!(pick(Priority::Recent, &all, Some(1)), Some(0));
+    }
+
+    #[test]
+    fn recent_ties_go_to_the_first_listed() {
+        let all = [Seen::default(), seen(false, None), seen(false, None)];
+        assert_eq!(pick(Priority::Recent, &all, None), Some(1));
+    }
+
+    #[test]
+    fn recent_with_nothing_is_none() {
+        let all = [Seen::default(), Seen::default()];
+        assert_eq!(pick(Priority::Recent, &all, None), None);
+    }
+
+    #[test]
+    fn playing_takes_the_first_playing() {
+        let all = [seen(false, None), seen(true, Some(60)), seen(true, Some(5))];
+        assert_eq!(pick(Priority::Playing, &all, Some(0)), Some(1));
+    }
+
+    #[test]
+    fn playing_keeps_the_shown_one_when_all_paused() {
+        let all = [seen(false, None), seen(false, None)];
+        assert_eq!(pick(Priority::Playing, &all, Some(1)), Some(1));
+        assert_eq!(pick(Priority::Playing, &all, None), None);
+    }
+
+    #[test]
+    fn order_takes_the_first_with_a_track() {
+        let all = [Seen::default(), seen(false, None), seen(true, Some(5))];
+        assert_eq!(pick(Priority::Order, &all, None), Some(1));
+    }
+}
@@ -1,14 +1,37 @@
-use rspotify::model::PlayableItem;
+use crate::player::{BoxFuture, Control, Device, PlayerBackend, QueueItem, Repeat};
+use rspotify::model::{
+    AlbumId, PlayContextId, PlayableId, PlayableItem, PlaylistId, RepeatState, SearchResult, SearchType,
+    TrackId,
+};
 use rspotify::prelude::*;
 use rspotify::AuthCodeSpotify;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
 
 pub fn fmt_time(ms: u64) -> String {
     let s = ms / 1000;
     format!("{}:{:02}", s / 60, s % 60)
 }
 
+/// Parses "3:54", "1:02:03" or plain seconds into milliseconds.
+pub fn parse_time(s: &str) -> Option<u64> {
+    let mut secs = 0u64;
+    for part in s.trim().split(':') {
+        secs = secs * 60 + part.parse::<u64>().ok()?;
+    }
+    Some(secs * 1000)
+}
+
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct NowPlaying {
+    #[serde(default)]
+    pub id: Option<String>,
     pub artist: String,
     pub track: String,
     pub album: String,
@@ -29,6 +52,9 @@ pub struct NowPlaying {
 
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct DeviceInfo {
+    /// Spotify Connect device ID, for moving playback to it.
+    #[serde(default)]
+    pub id: Option<String>,
     pub name: String,
     pub device_type: String,
     pub volume: Option<u32>,
@@ -57,6 +83,49 @@ pub struct State {
     pub now_playing: Option<NowPlaying>,
     pub fetched_at_ms: u64,
     pub lyrics: Option<crate::lyrics::Lyrics>,
+    /// Saved offset for this track and lyrics source, added to the user's own.
+    #[serde(default)]
+    pub track_offset_ms: i64,
+    /// Saved latency offset for the playing device.
+    #[serde(default)]
+    pub device_offset_ms: i64,
+    /// A-B loop over a range of lyric lines, cleared on track change.
+    #[serde(default)]
+    pub looping: Option<Loop>,
+    /// Which of the daemon's players `now_playing` came from, e.g. "spotify".
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct Loop {
+    /// First and last line of the range, 0-based.
+    pub from: usize,
+    pub to: usize,
+    /// Where the first line starts and the last one ends, in lyrics time.
+    pub start_ms: u64,
+    pub end_ms: u64,
+    /// Jumps back still to do; `None` loops until cleared.
+    pub remaining: Option<u32>,
+}
+
+impl State {
+    /// The saved track and device offsets; clients add the user's own on top.
+    pub fn offset_ms(&self) -> i64 {
+        self.track_offset_ms + self.device_offset_ms
+    }
+
+    /// Now playing with the progress moved on by the time since the daemon polled.
+    pub fn estimate(&self) -> Option<NowPlaying> {
+        let mut np = self.now_playing.clone()?;
+        if np.is_playing {
+            let elapsed = now_ms().saturating_sub(self.fetched_at_ms);
+            let estimated = (np.progress_ms + elapsed).min(np.duration_ms);
+            np.progress_ms = estimated;
+            np.progress = fmt_time(estimated);
+        }
+        Some(np)
+    }
 }
 
 pub async fn now_playing(spotify: &AuthCodeSpotify) -> Option<NowPlaying> {
@@ -70,6 +139,7 @@ pub async fn now_playing(spotify: &AuthCodeSpotify) -> Option<NowPlaying> {
         .unwrap_or(0);
 
     let device = Some(DeviceInfo {
+        id: ctx.device.id,
         name: ctx.device.name,
         device_type: format!("{:?}", ctx.device._type),
         volume: ctx.device.volume_percent,
@@ -100,6 +170,7 @@ pub async fn now_playing(spotify: &AuthCodeSpotify) -> Option<NowPlaying> {
                 .get("spotify")
                 .cloned();
             Some(NowPlaying {
+                id: track.id.as_ref().map(|id| id.id().to_string()),
                 artist,
                 track: track.name,
                 album: track.album.name,
@@ -123,6 +194,7 @@ pub async fn now_playing(spotify: &AuthCodeSpotify) -> Option<NowPlaying> {
             let album_art = ep.images.first().map(|img| img.url.clone());
             let spotify_url = ep.external_urls.get("spotify").cloned();
             Some(NowPlaying {
+                id: Some(ep.id.id().to_string()),
                 artist: ep.show.name,
                 track: ep.name,
                 album: String::new(),
@@ -143,3 +215,177 @@ pub async fn now_playing(spotify: &AuthCodeSpotify) -> Option<NowPlaying> {
         }
     }
 }
+
+fn queue_item(item: PlayableItem) -> QueueItem {
+    match item {
+        PlayableItem::Track(t) => QueueItem {
+            uri: t.id.as_ref().map(|id| id.uri()),
+            artist: t.artists.iter().map(|a| a.name.as_str()).collect::<Vec<_>>().join(", "),
+            track: t.name,
+            duration_ms: t.duration.num_milliseconds() as u64,
+        },
+        PlayableItem::Episode(ep) => QueueItem {
+            uri: Some(ep.id.uri()),
+            artist: ep.show.name,
+            track: ep.name,
+            duration_ms: ep.duration.num_milliseconds() as u64,
+        },
+    }
+}
+
+pub async fn queue(spotify: &AuthCodeSpotify) -> Result<Vec<QueueItem>, String> {
+    let q = spotify.current_user_queue().await.map_err(|e| e.to_string())?;
+    Ok(q.queue.into_iter().map(queue_item).collect())
+}
+
+// there's no jumping ahead in Spotify's queue, so this skips everything
+// before it
+async fn skip_queued(spotify: &AuthCodeSpotify, n: usize) -> Result<(), String> {
+    for _ in 0..n {
+        spotify.next_track(None).await.map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Queues a track given as an ID, URI or link, or the top hit for a search.
+pub async fn enqueue(spotify: &AuthCodeSpotify, query: &str) -> Result<QueueItem, String> {
+    let query = query.trim();
+    let is_id = query.starts_with("spotify:track:") || query.contains("open.spotify.com/track/");
+    let track = if is_id {
+        let id = TrackId::from_id(crate::sync::parse_id(query, "track")).map_err(|e| e.to_string())?;
+        spotify.track(id, None).await.map_err(|e| e.to_string())?
+    } else {
+        let result = spotify
+            .search(query, SearchType::Track, None, None, Some(1), None)
+            .await
+            .map_err(|e| e.to_string())?;
+        match result {
+            SearchResult::Tracks(page) => page.items.into_iter().next(),
+            _ => None,
+        }
+        .ok_or_else(|| format!("no track found for \"{query}\""))?
+    };
+    let id = track.id.clone().ok_or("that track can't be queued (local file)")?;
+    spotify
+        .add_item_to_queue(PlayableId::Track(id), None)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(queue_item(PlayableItem::Track(track)))
+}
+
+pub async fn devices(spotify: &AuthCodeSpotify) -> Result<Vec<Device>, String> {
+    let devices = spotify.device().await.map_err(|e| e.to_string())?;
+    Ok(devices
+        .into_iter()
+        .map(|d| Device {
+            info: DeviceInfo {
+                id: d.id,
+                name: d.name,
+                device_type: format!("{:?}", d._type),
+                volume: d.volume_percent,
+            },
+            active: d.is_active,
+        })
+        .collect())
+}
+
+impl PlayerBackend for AuthCodeSpotify {
+    fn now_playing(&self) -> BoxFuture<'_, Option<NowPlaying>> {
+        Box::pin(now_playing(self))
+    }
+
+    fn control(&self, control: Control) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(async move {
+            match control {
+                Control::Play => self.resume_playback(None, None).await,
+                Control::Pause => self.pause_playback(None).await,
+                Control::Next => self.next_track(None).await,
+                Control::Prev => self.previous_track(None).await,
+                Control::Volume(v) => self.volume(v, None).await,
+                Control::Seek(ms) => {
+                    self.seek_track(chrono::Duration::milliseconds(ms as i64), None).await
+                }
+                Control::Shuffle(on) => self.shuffle(on, None).await,
+                Control::Repeat(r) => {
+                    let state = match r {
+                        Repeat::Off => RepeatState::Off,
+                        Repeat::Context => RepeatState::Context,
+                        Repeat::Track => RepeatState::Track,
+                    };
+                    self.repeat(state, None).await
+                }
+                // albums and playlists play as a context, from the top
+                Control::PlayUri { uri, .. } if uri.starts_with("spotify:album:") => {
+                    let id = AlbumId::from_uri(&uri).map_err(|e| e.to_string())?;
+                    self.start_context_playback(PlayContextId::Album(id), None, None, None).await
+                }
+                Control::PlayUri { uri, .. } if uri.starts_with("spotify:playlist:") => {
+                    let id = PlaylistId::from_uri(&uri).map_err(|e| e.to_string())?;
+                    self.start_context_playback(PlayContextId::Playlist(id), None, None, None).await
+                }
+                Control::PlayUri { uri, position_ms } => {
+                    let id = TrackId::from_id_or_uri(&uri).map_err(|e| e.to_string())?;
+                    self.start_uris_playback(
+                        [PlayableId::Track(id)],
+                        None,
+                        None,
+                        position_ms.map(|ms| chrono::Duration::milliseconds(ms as i64)),
+                    )
+                    .await
+                }
+            }
+            .map_err(|e| e.to_string())
+        })
+    }
+
+    fn has_queue(&self) -> bool {
+        true
+    }
+
+    fn queue(&self) -> BoxFuture<'_, Result<Vec<QueueItem>, String>> {
+        Box::pin(queue(self))
+    }
+
+    fn enqueue<'a>(&'a self, query: &'a str) -> BoxFuture<'a, Result<QueueItem, String>> {
+        Box::pin(enqueue(self, query))
+    }
+
+    fn skip_queued(&self, n: usize) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(skip_queued(self, n))
+    }
+
+    fn has_devices(&self) -> bool {
+        true
+    }
+
+    fn devices(&self) -> BoxFuture<'_, Result<Vec<Device>, String>> {
+        Box::pin(devices(self))
+    }
+
+    fn transfer<'a>(&'a self, device_id: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            self.transfer_playback(device_id, None).await.map_err(|e| e.to_string())
+        })
+    }
+}
+
+/// Finds the Spotify track for an artist and title, returning its ID.
+pub async fn find_track(
+    spotify: &AuthCodeSpotify,
+    artist: &str,
+    title: &str,
+) -> Result<Option<String>, String> {
+    // field filters first, then a loose query for titles with odd punctuation
+    for q in [format!("track:{title} artist:{artist}"), format!("{artist} {title}")] {
+        let result = spotify
+            .search(&q, SearchType::Track, None, None, Some(5), None)
+            .await
+            .map_err(|e| e.to_string())?;
+        if let SearchResult::Tracks(page) = result
+            && let Some(id) = page.items.into_iter().find_map(|t| t.id)
+        {
+            return Ok(Some(id.id().to_string()));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,200 @@
+use crate::cache;
+use crate::export::{self, Format};
+use crate::lyrics::{self, Lyrics, Query};
+use rspotify::model::{AlbumId, PlayableItem, PlaylistId, SimplifiedArtist};
+use rspotify::prelude::*;
+use rspotify::AuthCodeSpotify;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+// pause after each LRCLIB request, per worker
+const REQUEST_DELAY: Duration = Duration::from_millis(250);
+
+#[derive(Clone, Copy)]
+pub enum Kind {
+    Playlist,
+    Album,
+}
+
+pub struct Options {
+    pub dir: Option<PathBuf>,
+    pub cache: bool,
+    pub jobs: usize,
+}
+
+enum Outcome {
+    Synced,
+    Plain,
+    Missing,
+}
+
+/// Accepts a bare ID, a `spotify:<kind>:<id>` URI or an open.spotify.com URL.
+pub fn parse_id(input: &str, kind: &str) -> String {
+    let marker = format!("/{kind}/");
+    match input.split_once(marker.as_str()) {
+        Some((_, rest)) => rest.split(['?', '/']).next().unwrap_or(rest).to_string(),
+        None => input.rsplit(':').next().unwrap_or(input).to_string(),
+    }
+}
+
+fn join_artists(artists: &[SimplifiedArtist]) -> String {
+    artists
+        .iter()
+        .map(|a| a.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+async fn playlist_tracks(spotify: &AuthCodeSpotify, id: &str) -> Result<Vec<Query>, String> {
+    let id = PlaylistId::from_id(id).map_err(|e| e.to_string())?;
+    let mut queries = Vec::new();
+    let mut offset = 0;
+    loop {
+        let page = spotify
+            .playlist_items_manual(id.as_ref(), None, None, Some(100), Some(offset))
+            .await
+            .map_err(|e| e.to_string())?;
+        for item in &page.items {
+            if let Some(PlayableItem::Track(t)) = &item.track {
+                queries.push(Query {
+                    artist: join_artists(&t.artists),
+                    title: t.name.clone(),
+                    album: t.album.name.clone(),
+                    duration_ms: t.duration.num_milliseconds() as u64,
+                });
+            }
+        }
+        offset += page.items.len() as u32;
+        if page.next.is_none() || page.items.is_empty() {
+            return Ok(queries);
+        }
+    }
+}
+
+async fn album_tracks(spotify: &AuthCodeSpotify, id: &str) -> Result<Vec<Query>, String> {
+    let id = AlbumId::from_id(id).map_err(|e| e.to_string())?;
+    let album = spotify.album(id.as_ref(), None).await.map_err(|e| e.to_string())?;
+    let mut queries = Vec::new();
+    let mut offset = 0;
+    loop {
+        let page = spotify
+            .album_track_manual(id.as_ref(), None, Some(50), Some(offset))
+            .await
+            .map_err(|e| e.to_string())?;
+        for t in &page.items {
+            queries.push(Query {
+                artist: join_artists(&t.artists),
+                title: t.name.clone(),
+                album: album.name.clone(),
+                duration_ms: t.duration.num_milliseconds() as u64,
+            });
+        }
+        offset += page.items.len() as u32;
+        if page.next.is_none() || page.items.is_empty() {
+            return Ok(queries);
+        }
+    }
+}
+
+fn file_name(q: &Query, ext: &str) -> String {
+    let name = format!("{} - {}.{ext}", q.artist, q.title);
+    name.replace(['/', '\0'], "_")
+}
+
+fn write_file(dir: &Path, q: &Query, ly: &Lyrics) -> Result<(), String> {
+    let (ext, format) = if ly.synced { ("lrc", Format::Lrc) } else { ("txt", Format::Txt) };
+    let contents = export::render(format, q, ly, 0)?;
+    let path = dir.join(file_name(q, ext));
+    std::fs::write(&path, contents).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+async fn sync_one(q: &Query, opts: &Options) -> Result<Outcome, String> {
+    let cached = if opts.cache { cache::load(q) } else { None };
+    let ly = match cached {
+        Some(ly) => Some(ly),
+        None => {
+            let ly = lyrics::fetch_remote(q).await;
+            tokio::time::sleep(REQUEST_DELAY).await;
+            if let Some(ly) = ly.as_ref()
+                && opts.cache
+            {
+                cache::store(q, ly);
+            }
+            ly
+        }
+    };
+    let Some(ly) = ly else { return Ok(Outcome::Missing) };
+    if let Some(dir) = &opts.dir {
+        write_file(dir, q, &ly)?;
+    }
+    Ok(if ly.synced { Outcome::Synced } else { Outcome::Plain })
+}
+
+fn print_group(label: &str, names: &[String]) {
+    println!("{label} ({}):", names.len());
+    for name in names {
+        println!("  {name}");
+    }
+}
+
+pub async fn run(
+    spotify: &AuthCodeSpotify,
+    kind: Kind,
+    input: &str,
+    opts: Options,
+) -> Result<(), String> {
+    let queries = match kind {
+        Kind::Playlist => playlist_tracks(spotify, &parse_id(input, "playlist")).await?,
+        Kind::Album => album_tracks(spotify, &parse_id(input, "album")).await?,
+    };
+    if let Some(dir) = &opts.dir {
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+    }
+    eprintln!("fetching lyrics for {} tracks...", queries.len());
+
+    let opts = Arc::new(opts);
+    let permits = Arc::new(Semaphore::new(opts.jobs.max(1)));
+    let mut tasks = JoinSet::new();
+    let mut names = HashMap::new();
+    for q in queries {
+        let opts = opts.clone();
+        let permits = permits.clone();
+        let name = format!("{} - {}", q.artist, q.title);
+        let task = tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            sync_one(&q, &opts).await
+        });
+        names.insert(task.id(), name);
+    }
+
+    let (mut synced, mut plain, mut missing) = (Vec::new(), Vec::new(), Vec::new());
+    // a task that panicked counts as a failure for its track, not the end of the run
+    while let Some(joined) = tasks.join_next_with_id().await {
+        let (id, outcome) = match joined {
+            Ok((id, outcome)) => (id, outcome),
+            Err(e) => (e.id(), Err(format!("task failed: {e}"))),
+        };
+        let name = names.remove(&id).unwrap_or_default();
+        match outcome {
+            Ok(Outcome::Synced) => synced.push(name),
+            Ok(Outcome::Plain) => plain.push(name),
+            Ok(Outcome::Missing) => missing.push(name),
+            Err(e) => {
+                eprintln!("{name}: {e}");
+                missing.push(name);
+            }
+        }
+    }
+
+    for group in [&mut synced, &mut plain, &mut missing] {
+        group.sort();
+    }
+    print_group("synced", &synced);
+    print_group("plain", &plain);
+    print_group("none", &missing);
+    Ok(())
+}
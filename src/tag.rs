@@ -0,0 +1,176 @@
+use crate::export::{self, Format};
+use crate::lyrics::{self, Lyrics, Query};
+use lofty::config::{ParseOptions, WriteOptions};
+use lofty::file::FileType;
+use lofty::id3::v2::{
+    BinaryFrame, Frame, FrameId, Id3v2Tag, SyncTextContentType, SynchronizedTextFrame,
+    TimestampFormat, UnsynchronizedTextFrame,
+};
+use lofty::mpeg::MpegFile;
+use lofty::prelude::*;
+use lofty::TextEncoding;
+use std::borrow::Cow;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+const AUDIO_EXTS: &[&str] = &["mp3", "flac", "ogg", "oga", "opus", "m4a", "mp4"];
+
+pub struct Options {
+    pub dry_run: bool,
+    pub skip_existing: bool,
+    pub sylt: bool,
+}
+
+fn walk(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        // file_type doesn't follow symlinks, so a link back up the tree can't loop
+        let Ok(kind) = entry.file_type() else { continue };
+        if kind.is_dir() {
+            walk(&path, out);
+        } else if path.is_file()
+            && path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| AUDIO_EXTS.contains(&e.to_lowercase().as_str()))
+        {
+            out.push(path);
+        }
+    }
+}
+
+fn read_query(path: &Path) -> Result<(Query, FileType, bool), String> {
+    let file = lofty::read_from_path(path).map_err(|e| e.to_string())?;
+    let tag = file.primary_tag().or_else(|| file.first_tag()).ok_or("no tags")?;
+    let artist = tag.artist().ok_or("no artist tag")?.to_string();
+    let title = tag.title().ok_or("no title tag")?.to_string();
+    let album = tag.album().map(|a| a.to_string()).unwrap_or_default();
+    let has_lyrics = tag.get_string(ItemKey::Lyrics).is_some()
+        || tag.get_string(ItemKey::UnsyncLyrics).is_some();
+    let query = Query {
+        artist,
+        title,
+        album,
+        duration_ms: file.properties().duration().as_millis() as u64,
+    };
+    Ok((query, file.file_type(), has_lyrics))
+}
+
+fn sylt_id() -> FrameId<'static> {
+    FrameId::Valid(Cow::Borrowed("SYLT"))
+}
+
+fn mpeg_tag(path: &Path) -> Result<Id3v2Tag, String> {
+    let mut f = File::open(path).map_err(|e| e.to_string())?;
+    let file = MpegFile::read_from(&mut f, ParseOptions::new()).map_err(|e| e.to_string())?;
+    Ok(file.id3v2().cloned().unwrap_or_default())
+}
+
+// ID3v2 gets LRC text in USLT, plus a millisecond SYLT frame when asked for
+fn write_mpeg(path: &Path, text: &str, ly: &Lyrics, sylt: bool) -> Result<(), String> {
+    let mut tag = mpeg_tag(path)?;
+    let _ = tag.remove(&FrameId::Valid(Cow::Borrowed("USLT")));
+    let _ = tag.remove(&sylt_id());
+    tag.insert(Frame::UnsynchronizedText(UnsynchronizedTextFrame::new(
+        TextEncoding::UTF8,
+        *b"eng",
+        "",
+        text.to_string(),
+    )));
+    if sylt && ly.synced {
+        let content = ly.lines.iter().map(|l| (l.time_ms as u32, l.text.clone())).collect();
+        let frame = SynchronizedTextFrame::new(
+            TextEncoding::UTF8,
+            *b"eng",
+            TimestampFormat::MS,
+            SyncTextContentType::Lyrics,
+            None,
+            content,
+        );
+        let bytes = frame.as_bytes(WriteOptions::default()).map_err(|e| e.to_string())?;
+        tag.insert(Frame::Binary(BinaryFrame::new(sylt_id(), bytes)));
+    }
+    tag.save_to_path(path, WriteOptions::default()).map_err(|e| e.to_string())
+}
+
+// Vorbis comments get LYRICS, MP4 gets ©lyr
+fn write_generic(path: &Path, text: &str) -> Result<(), String> {
+    let mut file = lofty::read_from_path(path).map_err(|e| e.to_string())?;
+    let tag_type = file.primary_tag_type();
+    if file.tag(tag_type).is_none() {
+        file.insert_tag(lofty::tag::Tag::new(tag_type));
+    }
+    let tag = file.tag_mut(tag_type).ok_or("no writable tag")?;
+    tag.insert_text(ItemKey::Lyrics, text.to_string());
+    tag.save_to_path(path, WriteOptions::default()).map_err(|e| e.to_string())
+}
+
+fn has_sylt(path: &Path) -> bool {
+    mpeg_tag(path).is_ok_and(|t| t.get(&sylt_id()).is_some())
+}
+
+pub async fn run(dir: &Path, opts: Options) -> Result<(), String> {
+    let mut files = Vec::new();
+    walk(dir, &mut files);
+    files.sort();
+    if files.is_empty() {
+        return Err(format!("no audio files in {}", dir.display()));
+    }
+
+    let (mut synced, mut plain, mut skipped, mut missing) = (0, 0, 0, 0);
+    for path in &files {
+        let shown = path.strip_prefix(dir).unwrap_or(path).display();
+        let (q, file_type, mut existing) = match read_query(path) {
+            Ok(info) => info,
+            Err(e) => {
+                println!("skip   {shown}: {e}");
+                skipped += 1;
+                continue;
+            }
+        };
+        if file_type == FileType::Mpeg {
+            existing |= has_sylt(path);
+        }
+        if existing && opts.skip_existing {
+            println!("skip   {shown}: has lyrics");
+            skipped += 1;
+            continue;
+        }
+
+        let Some(ly) = lyrics::fetch(&q).await else {
+            println!("none   {shown}");
+            missing += 1;
+            continue;
+        };
+        let format = if ly.synced { Format::Lrc } else { Format::Txt };
+        let text = export::render(format, &q, &ly, 0)?;
+        let kind = if ly.synced { "synced" } else { "plain " };
+
+        if !opts.dry_run {
+            let written = if file_type == FileType::Mpeg {
+                write_mpeg(path, &text, &ly, opts.sylt)
+            } else {
+                write_generic(path, &text)
+            };
+            if let Err(e) = written {
+                println!("error  {shown}: {e}");
+                skipped += 1;
+                continue;
+            }
+        }
+        println!("{kind} {shown}");
+        if ly.synced {
+            synced += 1;
+        } else {
+            plain += 1;
+        }
+    }
+
+    let verb = if opts.dry_run { "would tag" } else { "tagged" };
+    println!(
+        "\n{verb} {} files ({synced} synced, {plain} plain), {missing} without lyrics, {skipped} skipped",
+        synced + plain
+    );
+    Ok(())
+}
@@ -1,4 +1,8 @@
-use crate::{client, lyrics};
+use crate::cloze::Cloze;
+use crate::editor::Editor;
+use crate::player::{Device, QueueItem};
+use crate::spotify::fmt_time;
+use crate::{client, config, lyrics, quotes};
 use crossterm::{
     event::{self, Event, KeyCode},
     execute,
@@ -9,7 +13,7 @@ use ratatui::{
     layout::{Alignment, Constraint, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, List, ListItem, ListState, Padding, Paragraph},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Padding, Paragraph},
     Frame, Terminal,
 };
 use ratatui_image::{picker::Picker, protocol::StatefulProtocol, StatefulImage};
@@ -28,6 +32,67 @@ struct App {
     picker: Option<Picker>,
     art: Option<StatefulProtocol>,
     art_url: String,
+    editor: Option<Editor>,
+    // first line of a quote selection, set with `v`
+    mark: Option<usize>,
+    flash: Option<(String, Instant)>,
+    cloze: Option<Cloze>,
+    queue: Option<ListPanel<QueueItem>>,
+    devices: Option<ListPanel<Device>>,
+}
+
+// a list loaded when it's opened: the queue (`u`, Enter skips to the
+// selected track) or Spotify Connect devices (`d`, Enter moves playback)
+struct ListPanel<T> {
+    items: Result<Vec<T>, String>,
+    list_state: ListState,
+}
+
+impl<T> ListPanel<T> {
+    fn new(items: Result<Vec<T>, String>, first: impl Fn(&[T]) -> usize) -> Self {
+        let mut list_state = ListState::default();
+        if let Ok(items) = &items
+            && !items.is_empty()
+        {
+            list_state.select(Some(first(items)));
+        }
+        Self { items, list_state }
+    }
+
+    fn len(&self) -> usize {
+        self.items.as_ref().map_or(0, |i| i.len())
+    }
+
+    fn selected(&self) -> Option<(usize, &T)> {
+        let i = self.list_state.selected()?;
+        Some((i, self.items.as_ref().ok()?.get(i)?))
+    }
+
+    // up/down and k/j; false for any other key
+    fn nav(&mut self, code: KeyCode) -> bool {
+        let (len, sel) = (self.len(), self.list_state.selected().unwrap_or(0));
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => self.list_state.select(Some(sel.saturating_sub(1))),
+            KeyCode::Down | KeyCode::Char('j') if len > 0 => {
+                self.list_state.select(Some((sel + 1).min(len - 1)))
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl ListPanel<QueueItem> {
+    fn load() -> Self {
+        Self::new(client::fetch_queue(), |_| 0)
+    }
+}
+
+impl ListPanel<Device> {
+    // starts on the device playing now
+    fn load() -> Self {
+        Self::new(client::fetch_devices(), |d| d.iter().position(|d| d.active).unwrap_or(0))
+    }
 }
 
 impl App {
@@ -42,14 +107,168 @@ impl App {
             picker,
             art: None,
             art_url: String::new(),
+            editor: None,
+            mark: None,
+            flash: None,
+            cloze: None,
+            queue: None,
+            devices: None,
         }
     }
 
     fn progress_ms(&self) -> u64 {
-        let Some(np) = client::estimate(&self.state) else {
+        let Some(np) = self.state.estimate() else {
             return 0;
         };
-        (np.progress_ms as i64 + self.offset_ms).max(0) as u64
+        (np.progress_ms as i64 + self.total_offset_ms()).max(0) as u64
+    }
+
+    fn total_offset_ms(&self) -> i64 {
+        self.offset_ms + self.state.offset_ms()
+    }
+
+    // saved by the daemon for this track; 0 clears it
+    fn set_track_offset(&mut self, ms: i64) {
+        if client::send_command(&format!(r#"{{"cmd":"offset","arg":"{ms}"}}"#)).is_ok() {
+            self.state.track_offset_ms = ms;
+        }
+    }
+
+    // where the song actually is, without the display offset
+    fn position_ms(&self) -> u64 {
+        self.state.estimate().map_or(0, |np| np.progress_ms)
+    }
+
+    fn say(&mut self, msg: impl Into<String>) {
+        self.flash = Some((msg.into(), Instant::now()));
+    }
+
+    // saves the marked range, or just the current line
+    fn save_quote(&mut self) {
+        let Some(cur) = self.list_state.selected() else {
+            self.say("no current line to quote");
+            return;
+        };
+        let (from, to) = match self.mark.take() {
+            Some(m) => (m.min(cur), m.max(cur)),
+            None => (cur, cur),
+        };
+        match quotes::save(&self.state, from, to) {
+            Ok(q) => self.say(format!("quoted {} line(s)", q.lines.len())),
+            Err(e) => self.say(e),
+        }
+    }
+
+    fn toggle_mark(&mut self) {
+        self.mark = match self.mark {
+            Some(_) => None,
+            None => self.list_state.selected(),
+        };
+    }
+
+    // loops the marked range, or just the current line; again to stop
+    fn toggle_loop(&mut self) {
+        let arg = if self.state.looping.is_some() {
+            "off".to_string()
+        } else {
+            let Some(cur) = self.list_state.selected() else {
+                self.say("no current line to loop");
+                return;
+            };
+            let (from, to) = match self.mark.take() {
+                Some(m) => (m.min(cur), m.max(cur)),
+                None => (cur, cur),
+            };
+            format!("{} {}", from + 1, to + 1)
+        };
+        match client::send_command(&format!(r#"{{"cmd":"loop","arg":"{arg}"}}"#)) {
+            Ok(_) => self.last_fetch = Instant::now() - Duration::from_secs(10),
+            Err(e) => self.say(e),
+        }
+    }
+
+    fn toggle_cloze(&mut self) {
+        self.cloze = match self.cloze {
+            Some(_) => None,
+            None => {
+                let step = config::load().map_or(20, |c| c.cloze_step_percent);
+                Some(Cloze::new(step, &self.current_track))
+            }
+        };
+    }
+
+    fn toggle_queue(&mut self) {
+        self.queue = match self.queue {
+            Some(_) => None,
+            None => Some(ListPanel::<QueueItem>::load()),
+        };
+    }
+
+    fn skip_to_queued(&mut self) {
+        let Some((sel, _)) = self.queue.as_ref().and_then(|q| q.selected()) else { return };
+        let cmd = format!(r#"{{"cmd":"queue_skip","arg":"{}"}}"#, sel + 1);
+        match client::send_command(&cmd) {
+            Ok(_) => {
+                self.queue = Some(ListPanel::<QueueItem>::load());
+                self.last_fetch = Instant::now() - Duration::from_secs(10);
+            }
+            Err(e) => self.say(e),
+        }
+    }
+
+    fn toggle_devices(&mut self) {
+        self.devices = match self.devices {
+            Some(_) => None,
+            None => Some(ListPanel::<Device>::load()),
+        };
+    }
+
+    fn switch_device(&mut self) {
+        let Some(panel) = self.devices.take() else { return };
+        let Some((_, device)) = panel.selected() else { return };
+        let Some(id) = &device.info.id else {
+            self.say("that device can't take playback");
+            return;
+        };
+        let cmd = serde_json::json!({"cmd": "device", "arg": id}).to_string();
+        match client::send_command(&cmd) {
+            Ok(_) => {
+                self.say(format!("playing on {}", device.info.name));
+                self.last_fetch = Instant::now() - Duration::from_secs(10);
+            }
+            Err(e) => self.say(e),
+        }
+    }
+
+    fn start_typing(&mut self) {
+        let (Some(cz), Some(ly)) = (self.cloze.as_mut(), &self.state.lyrics) else { return };
+        if !cz.start_typing(&ly.lines, self.list_state.selected()) {
+            self.say("no line left to type");
+        }
+    }
+
+    fn submit_typing(&mut self) {
+        let (Some(cz), Some(ly)) = (self.cloze.as_mut(), &self.state.lyrics) else { return };
+        if let Some((score, real)) = cz.submit(&ly.lines) {
+            self.say(format!("{:.0}%  {real}", score * 100.0));
+        }
+    }
+
+    fn open_editor(&mut self) {
+        let Some(np) = &self.state.now_playing else { return };
+        // state.lyrics may be masked, and that mustn't end up saved or on LRCLIB
+        let ly = match client::fetch_raw_lyrics() {
+            Ok(Some(ly)) => ly,
+            Ok(None) => return,
+            Err(e) => return self.say(e),
+        };
+        let query = lyrics::Query {
+            artist: np.artist.clone(),
+            title: np.track.clone(),
+            album: np.album.clone(),
+            duration_ms: np.duration_ms,
+        };
+        self.editor = Some(Editor::new(query, &ly));
     }
 
     fn update(&mut self) {
@@ -68,14 +287,28 @@ impl App {
         if track_key != self.current_track {
             self.current_track = track_key;
             self.list_state = ListState::default();
+            self.editor = None;
+            self.mark = None;
+            if let Some(cz) = self.cloze.as_mut() {
+                cz.retrack(&self.current_track);
+            }
+            if self.queue.is_some() {
+                self.queue = Some(ListPanel::<QueueItem>::load());
+            }
             self.update_art();
         }
 
-        if let Some(ly) = &self.state.lyrics {
-            if ly.synced {
-                self.list_state
-                    .select(lyrics::current_line_index(&ly.lines, self.progress_ms()));
-            }
+        if let Some(ly) = &self.state.lyrics
+            && ly.synced
+        {
+            self.list_state
+                .select(lyrics::current_line_index(&ly.lines, self.progress_ms()));
+        }
+
+        // time's up once the line being typed is sung
+        let typing = self.cloze.as_ref().and_then(|c| c.typing.as_ref()).map(|t| t.line);
+        if typing.is_some_and(|t| self.list_state.selected().is_some_and(|s| s >= t)) {
+            self.submit_typing();
         }
     }
 
@@ -98,10 +331,10 @@ impl App {
             return;
         }
 
-        if let Ok(bytes) = reqwest::blocking::get(url).and_then(|r| r.bytes()) {
-            if let Ok(img) = image::load_from_memory(&bytes) {
-                self.art = Some(picker.new_resize_protocol(img));
-            }
+        if let Ok(bytes) = reqwest::blocking::get(url).and_then(|r| r.bytes())
+            && let Ok(img) = image::load_from_memory(&bytes)
+        {
+            self.art = Some(picker.new_resize_protocol(img));
         }
     }
 }
@@ -117,11 +350,16 @@ fn ui(f: &mut Frame, app: &mut App) {
     ])
     .split(outer);
 
-    let np = client::estimate(&app.state);
+    let np = app.state.estimate();
 
     // --- header ---
     let header_area = chunks[1];
-    render_header(f, header_area, &np);
+    let flash = app
+        .flash
+        .as_ref()
+        .filter(|(_, at)| at.elapsed() < Duration::from_secs(3))
+        .map(|(msg, _)| msg.as_str());
+    render_header(f, header_area, &np, status_line(app, flash));
 
     // --- separator ---
     let sep_area = chunks[2];
@@ -135,9 +373,25 @@ fn ui(f: &mut Frame, app: &mut App) {
     let lyrics_area = chunks[3];
     let lyrics_block = Block::default().padding(Padding::horizontal(2));
 
+    if let Some(editor) = &app.editor {
+        editor.render(f, lyrics_area, ACCENT, DIM);
+        return;
+    }
+    if let Some(panel) = app.queue.as_mut() {
+        render_queue(f, lyrics_area, panel);
+        return;
+    }
+
     match &app.state.lyrics {
         Some(ly) if !ly.lines.is_empty() => {
             let selected = app.list_state.selected();
+            let marked = |i: usize| match (app.mark, selected) {
+                (Some(m), Some(s)) => (m.min(s)..=m.max(s)).contains(&i),
+                _ => false,
+            };
+            let looped = |i: usize| app.state.looping.as_ref().is_some_and(|l| (l.from..=l.to).contains(&i));
+            let typing = app.cloze.as_ref().and_then(|c| c.typing.as_ref()).map(|t| t.line);
+            let progress = app.progress_ms();
             let items: Vec<ListItem> = ly
                 .lines
                 .iter()
@@ -147,6 +401,12 @@ fn ui(f: &mut Frame, app: &mut App) {
                         Style::default()
                             .fg(ACCENT)
                             .add_modifier(Modifier::BOLD)
+                    } else if typing == Some(i) {
+                        Style::default().fg(Color::Magenta)
+                    } else if marked(i) {
+                        Style::default().fg(Color::Yellow)
+                    } else if looped(i) {
+                        Style::default().fg(Color::Cyan)
                     } else {
                         let d = selected.map(|s| i.abs_diff(s)).unwrap_or(999);
                         if d <= 2 {
@@ -155,7 +415,11 @@ fn ui(f: &mut Frame, app: &mut App) {
                             Style::default().fg(DIM)
                         }
                     };
-                    let text = if l.text.is_empty() { " " } else { &l.text };
+                    let text = match &app.cloze {
+                        Some(cz) => cz.line(i, &l.text, selected, progress),
+                        None => l.text.clone(),
+                    };
+                    let text = if text.is_empty() { " ".to_string() } else { text };
                     ListItem::new(
                         Line::from(Span::styled(text, style)).alignment(Alignment::Center),
                     )
@@ -193,10 +457,36 @@ fn ui(f: &mut Frame, app: &mut App) {
     }
 }
 
+// header line 3: what's being typed, a passing message, or whatever is set
+// (offset, loop, cloze)
+fn status_line(app: &App, flash: Option<&str>) -> Line<'static> {
+    if let Some(t) = app.cloze.as_ref().and_then(|c| c.typing.as_ref()) {
+        let prompt = format!("line {}: {}_", t.line + 1, t.input);
+        return Line::from(Span::styled(prompt, Style::default().fg(Color::Magenta)));
+    }
+    if let Some(msg) = flash {
+        return Line::from(Span::styled(msg.to_string(), Style::default().fg(ACCENT)));
+    }
+    let mut status = Vec::new();
+    let offset_ms = app.total_offset_ms();
+    if offset_ms != 0 {
+        status.push(format!("offset {offset_ms:+}ms"));
+    }
+    if let Some(l) = &app.state.looping {
+        let times = l.remaining.map_or(String::new(), |n| format!(" \u{d7}{n}"));
+        status.push(format!("loop {}-{}{times}", l.from + 1, l.to + 1));
+    }
+    if let Some(cz) = &app.cloze {
+        status.push(cz.status());
+    }
+    Line::from(Span::styled(status.join("  "), Style::default().fg(DIM)))
+}
+
 fn render_header(
     f: &mut Frame,
     area: Rect,
     np: &Option<crate::spotify::NowPlaying>,
+    status: Line<'static>,
 ) {
     let text_area = Rect {
         x: area.x + 1,
@@ -227,7 +517,7 @@ fn render_header(
         }
         let sub = Line::from(sub_spans);
 
-        // line 3: elapsed ━━━━━━━━────────── total
+        // line 4: elapsed ━━━━━━━━────────── total
         let ratio = if n.duration_ms > 0 {
             (n.progress_ms as f64 / n.duration_ms as f64).min(1.0)
         } else {
@@ -250,7 +540,7 @@ fn render_header(
             Span::styled(&time_r, Style::default().fg(DIM)),
         ]);
 
-        let header = Paragraph::new(vec![title, sub, Line::raw(""), progress_line])
+        let header = Paragraph::new(vec![title, sub, status, progress_line])
             .alignment(Alignment::Center);
         f.render_widget(header, text_area);
     } else {
@@ -263,6 +553,167 @@ fn render_header(
     }
 }
 
+// `panel` as a list in `block`, or `empty` (or the error) in its place
+fn render_list<T>(
+    f: &mut Frame,
+    area: Rect,
+    block: Block,
+    panel: &mut ListPanel<T>,
+    empty: &str,
+    row: impl Fn(usize, &T) -> Line<'static>,
+) {
+    let items = match &panel.items {
+        Ok(items) if !items.is_empty() => items,
+        other => {
+            let msg = match other {
+                Err(e) => e.clone(),
+                Ok(_) => empty.to_string(),
+            };
+            let msg = Paragraph::new(Span::styled(msg, Style::default().fg(DIM)))
+                .block(block)
+                .alignment(Alignment::Center);
+            f.render_widget(msg, area);
+            return;
+        }
+    };
+    let items: Vec<ListItem> =
+        items.iter().enumerate().map(|(i, item)| ListItem::new(row(i, item))).collect();
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().fg(ACCENT).add_modifier(Modifier::BOLD))
+        .highlight_symbol("\u{25b8} ");
+    f.render_stateful_widget(list, area, &mut panel.list_state);
+}
+
+fn render_queue(f: &mut Frame, area: Rect, panel: &mut ListPanel<QueueItem>) {
+    let title = Line::from(Span::styled("up next", Style::default().fg(DIM)));
+    let block = Block::default()
+        .title(title.alignment(Alignment::Center))
+        .padding(Padding::horizontal(2));
+    let width = panel.len().to_string().len();
+    render_list(f, area, block, panel, "queue is empty", |i, item| {
+        Line::from(vec![
+            Span::styled(format!("{:>width$}. ", i + 1), Style::default().fg(DIM)),
+            Span::styled(item.track.clone(), Style::default().fg(Color::Gray)),
+            Span::styled(format!(" \u{2014} {}", item.artist), Style::default().fg(DIM)),
+            Span::styled(format!("  {}", fmt_time(item.duration_ms)), Style::default().fg(DIM)),
+        ])
+    });
+}
+
+// a box in the middle of `area`, at most `w` by `h`
+fn centered(area: Rect, w: u16, h: u16) -> Rect {
+    let w = w.min(area.width);
+    let h = h.min(area.height);
+    Rect::new(area.x + (area.width - w) / 2, area.y + (area.height - h) / 2, w, h)
+}
+
+fn render_devices(f: &mut Frame, panel: &mut ListPanel<Device>) {
+    let height = panel.len().max(1) as u16 + 2;
+    let area = centered(f.area(), 48, height);
+    f.render_widget(Clear, area);
+    let title = Line::from(Span::styled(" devices ", Style::default().fg(DIM)));
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(DIM))
+        .title(title.alignment(Alignment::Center))
+        .padding(Padding::horizontal(1));
+    render_list(f, area, block, panel, "no devices, open Spotify somewhere", |_, d| {
+        let name = match d.active {
+            true => Style::default().fg(Color::White),
+            false => Style::default().fg(Color::Gray),
+        };
+        let volume = d.info.volume.map_or(String::new(), |v| format!("  {v}%"));
+        Line::from(vec![
+            Span::styled(if d.active { "\u{25cf} " } else { "  " }, Style::default().fg(ACCENT)),
+            Span::styled(d.info.name.clone(), name),
+            Span::styled(format!("  {}{volume}", d.info.device_type), Style::default().fg(DIM)),
+        ])
+    });
+}
+
+// keys the device popup takes; it's modal, so it swallows the rest
+fn devices_key(app: &mut App, code: KeyCode) {
+    let Some(panel) = app.devices.as_mut() else { return };
+    if panel.nav(code) {
+        return;
+    }
+    match code {
+        KeyCode::Char('d' | 'q') | KeyCode::Esc => app.devices = None,
+        KeyCode::Enter => app.switch_device(),
+        _ => {}
+    }
+}
+
+// keys the queue panel takes; anything else falls through to playback
+fn queue_key(app: &mut App, code: KeyCode) -> bool {
+    let Some(panel) = app.queue.as_mut() else { return false };
+    if panel.nav(code) {
+        return true;
+    }
+    match code {
+        KeyCode::Char('u' | 'q') | KeyCode::Esc => app.queue = None,
+        KeyCode::Enter => app.skip_to_queued(),
+        _ => return false,
+    }
+    true
+}
+
+fn editor_key(app: &mut App, code: KeyCode) {
+    let now = app.position_ms();
+    let Some(ed) = app.editor.as_mut() else { return };
+    if code != KeyCode::Char('P') {
+        ed.cancel_publish();
+    }
+    match code {
+        KeyCode::Char('q') | KeyCode::Esc => app.editor = None,
+        KeyCode::Char(' ') | KeyCode::Enter => ed.stamp(now),
+        KeyCode::Char('u') | KeyCode::Backspace => ed.undo(),
+        KeyCode::Char('[') => ed.nudge(false),
+        KeyCode::Char(']') => ed.nudge(true),
+        KeyCode::Up => ed.up(),
+        KeyCode::Down => ed.down(),
+        KeyCode::Char('r') => {
+            if let Some(pos) = ed.replay_pos() {
+                let _ = client::send_command(&format!(r#"{{"cmd":"seek","arg":"{pos}"}}"#));
+                app.last_fetch = Instant::now() - Duration::from_secs(10);
+            }
+        }
+        KeyCode::Char('t') => {
+            let _ = client::send_command(r#"{"cmd":"toggle"}"#);
+            app.last_fetch = Instant::now() - Duration::from_secs(10);
+        }
+        KeyCode::Char('s') => match ed.save() {
+            Ok(()) => {
+                let _ = client::send_command(r#"{"cmd":"reload"}"#);
+                app.last_fetch = Instant::now() - Duration::from_secs(10);
+            }
+            Err(e) => ed.fail(e),
+        },
+        KeyCode::Char('P') => ed.publish(),
+        _ => {}
+    }
+}
+
+fn typing_key(app: &mut App, code: KeyCode) {
+    let Some(cz) = app.cloze.as_mut() else { return };
+    match code {
+        KeyCode::Esc => cz.typing = None,
+        KeyCode::Enter => app.submit_typing(),
+        KeyCode::Backspace => {
+            if let Some(t) = cz.typing.as_mut() {
+                t.input.pop();
+            }
+        }
+        KeyCode::Char(c) => {
+            if let Some(t) = cz.typing.as_mut() {
+                t.input.push(c);
+            }
+        }
+        _ => {}
+    }
+}
+
 pub fn run(offset_ms: i64) {
     enable_raw_mode().unwrap();
     let mut stdout = io::stdout();
@@ -274,62 +725,101 @@ pub fn run(offset_ms: i64) {
     app.update();
 
     loop {
-        terminal.draw(|f| ui(f, &mut app)).unwrap();
-
-        if event::poll(Duration::from_millis(100)).unwrap() {
-            if let Event::Key(key) = event::read().unwrap() {
-                match key.code {
-                    KeyCode::Char('q') | KeyCode::Esc => break,
-                    KeyCode::Char(' ') => {
-                        let _ = client::send_command(r#"{"cmd":"toggle"}"#);
-                        app.last_fetch = Instant::now() - Duration::from_secs(10);
-                    }
-                    KeyCode::Char('n') => {
-                        let _ = client::send_command(r#"{"cmd":"next"}"#);
-                        app.last_fetch = Instant::now() - Duration::from_secs(10);
-                    }
-                    KeyCode::Char('p') => {
-                        let _ = client::send_command(r#"{"cmd":"prev"}"#);
-                        app.last_fetch = Instant::now() - Duration::from_secs(10);
-                    }
-                    KeyCode::Char('+') | KeyCode::Char('=') => {
-                        let vol = app.state.now_playing.as_ref()
-                            .and_then(|n| n.device.as_ref())
-                            .and_then(|d| d.volume)
-                            .unwrap_or(50);
-                        let new_vol = (vol + 5).min(100);
-                        let _ = client::send_command(&format!(r#"{{"cmd":"volume","arg":"{new_vol}"}}"#));
-                        app.last_fetch = Instant::now() - Duration::from_secs(10);
-                    }
-                    KeyCode::Char('-') => {
-                        let vol = app.state.now_playing.as_ref()
-                            .and_then(|n| n.device.as_ref())
-                            .and_then(|d| d.volume)
-                            .unwrap_or(50);
-                        let new_vol = vol.saturating_sub(5);
-                        let _ = client::send_command(&format!(r#"{{"cmd":"volume","arg":"{new_vol}"}}"#));
-                        app.last_fetch = Instant::now() - Duration::from_secs(10);
-                    }
-                    KeyCode::Char('s') => {
-                        let _ = client::send_command(r#"{"cmd":"shuffle"}"#);
-                        app.last_fetch = Instant::now() - Duration::from_secs(10);
-                    }
-                    KeyCode::Char('r') => {
-                        let _ = client::send_command(r#"{"cmd":"repeat"}"#);
-                        app.last_fetch = Instant::now() - Duration::from_secs(10);
-                    }
-                    KeyCode::Left => {
-                        let pos = app.progress_ms().saturating_sub(10000);
-                        let _ = client::send_command(&format!(r#"{{"cmd":"seek","arg":"{pos}"}}"#));
-                        app.last_fetch = Instant::now() - Duration::from_secs(10);
-                    }
-                    KeyCode::Right => {
-                        let pos = app.progress_ms() + 10000;
-                        let _ = client::send_command(&format!(r#"{{"cmd":"seek","arg":"{pos}"}}"#));
-                        app.last_fetch = Instant::now() - Duration::from_secs(10);
-                    }
-                    _ => {}
+        terminal
+            .draw(|f| {
+                ui(f, &mut app);
+                if let Some(panel) = app.devices.as_mut() {
+                    render_devices(f, panel);
+                }
+            })
+            .unwrap();
+
+        if event::poll(Duration::from_millis(100)).unwrap()
+            && let Event::Key(key) = event::read().unwrap()
+        {
+            if app.editor.is_some() {
+                editor_key(&mut app, key.code);
+                app.update();
+                continue;
+            }
+            if app.cloze.as_ref().is_some_and(|c| c.typing.is_some()) {
+                typing_key(&mut app, key.code);
+                app.update();
+                continue;
+            }
+            if app.devices.is_some() {
+                devices_key(&mut app, key.code);
+                app.update();
+                continue;
+            }
+            if queue_key(&mut app, key.code) {
+                app.update();
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => break,
+                KeyCode::Char('u') => app.toggle_queue(),
+                KeyCode::Char('d') => app.toggle_devices(),
+                KeyCode::Char('e') => app.open_editor(),
+                KeyCode::Char('v') => app.toggle_mark(),
+                KeyCode::Char('y') => app.save_quote(),
+                KeyCode::Char('l') => app.toggle_loop(),
+                KeyCode::Char('c') => app.toggle_cloze(),
+                KeyCode::Char('>') => app.cloze.iter_mut().for_each(|c| c.harder()),
+                KeyCode::Char('<') => app.cloze.iter_mut().for_each(|c| c.easier()),
+                KeyCode::Char('i') => app.start_typing(),
+                KeyCode::Char('[') => app.set_track_offset(app.state.track_offset_ms - 100),
+                KeyCode::Char(']') => app.set_track_offset(app.state.track_offset_ms + 100),
+                KeyCode::Char('0') => app.set_track_offset(0),
+                KeyCode::Char(' ') => {
+                    let _ = client::send_command(r#"{"cmd":"toggle"}"#);
+                    app.last_fetch = Instant::now() - Duration::from_secs(10);
+                }
+                KeyCode::Char('n') => {
+                    let _ = client::send_command(r#"{"cmd":"next"}"#);
+                    app.last_fetch = Instant::now() - Duration::from_secs(10);
+                }
+                KeyCode::Char('p') => {
+                    let _ = client::send_command(r#"{"cmd":"prev"}"#);
+                    app.last_fetch = Instant::now() - Duration::from_secs(10);
+                }
+                KeyCode::Char('+') | KeyCode::Char('=') => {
+                    let vol = app.state.now_playing.as_ref()
+                        .and_then(|n| n.device.as_ref())
+                        .and_then(|d| d.volume)
+                        .unwrap_or(50);
+                    let new_vol = (vol + 5).min(100);
+                    let _ = client::send_command(&format!(r#"{{"cmd":"volume","arg":"{new_vol}"}}"#));
+                    app.last_fetch = Instant::now() - Duration::from_secs(10);
+                }
+                KeyCode::Char('-') => {
+                    let vol = app.state.now_playing.as_ref()
+                        .and_then(|n| n.device.as_ref())
+                        .and_then(|d| d.volume)
+                        .unwrap_or(50);
+                    let new_vol = vol.saturating_sub(5);
+                    let _ = client::send_command(&format!(r#"{{"cmd":"volume","arg":"{new_vol}"}}"#));
+                    app.last_fetch = Instant::now() - Duration::from_secs(10);
+                }
+                KeyCode::Char('s') => {
+                    let _ = client::send_command(r#"{"cmd":"shuffle"}"#);
+                    app.last_fetch = Instant::now() - Duration::from_secs(10);
+                }
+                KeyCode::Char('r') => {
+                    let _ = client::send_command(r#"{"cmd":"repeat"}"#);
+                    app.last_fetch = Instant::now() - Duration::from_secs(10);
+                }
+                KeyCode::Left => {
+                    let pos = app.progress_ms().saturating_sub(10000);
+                    let _ = client::send_command(&format!(r#"{{"cmd":"seek","arg":"{pos}"}}"#));
+                    app.last_fetch = Instant::now() - Duration::from_secs(10);
+                }
+                KeyCode::Right => {
+                    let pos = app.progress_ms() + 10000;
+                    let _ = client::send_command(&format!(r#"{{"cmd":"seek","arg":"{pos}"}}"#));
+                    app.last_fetch = Instant::now() - Duration::from_secs(10);
                 }
+                _ => {}
             }
         }
 
@@ -0,0 +1,293 @@
+use crate::card::Preset;
+use crate::draw::{self, Align, Fonts, Palette};
+use crate::lyrics::{self, LyricLine};
+use crate::client;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, Rgba, RgbaImage};
+use std::fs::File;
+use std::io::BufWriter;
+
+// the scroll from one line to the next, split into this many frames
+const SCROLL_FRAMES: u64 = 6;
+const SCROLL_MS: u64 = 300;
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Gif,
+    Apng,
+}
+
+impl Format {
+    /// Picks the container from the output's extension.
+    pub fn from_path(path: &str) -> Option<Self> {
+        match path.rsplit_once('.')?.1.to_lowercase().as_str() {
+            "gif" => Some(Self::Gif),
+            "png" | "apng" => Some(Self::Apng),
+            _ => None,
+        }
+    }
+}
+
+pub struct Clip {
+    pub lines: Vec<LyricLine>,
+    pub artist: String,
+    pub title: String,
+    pub art_url: Option<String>,
+    pub from_ms: u64,
+    pub to_ms: u64,
+    /// Positive shows lines earlier, as everywhere else.
+    pub offset_ms: i64,
+}
+
+// smaller than the card presets; every frame gets encoded separately
+fn size(preset: Preset) -> (u32, u32) {
+    match preset {
+        Preset::Square => (720, 720),
+        Preset::Story => (720, 1280),
+        Preset::Wide => (1280, 720),
+    }
+}
+
+/// Background, header and the lyrics column, laid out once for every frame.
+struct Scene {
+    fonts: Fonts,
+    palette: Palette,
+    faint: Rgba<u8>,
+    base: RgbaImage,
+    // x, y, width, height of the scrolling lyrics
+    panel: (u32, u32, u32, u32),
+    px: f32,
+    rows: Vec<Vec<String>>,
+    tops: Vec<f32>,
+    heights: Vec<f32>,
+}
+
+fn mix(a: Rgba<u8>, b: Rgba<u8>, t: f32) -> Rgba<u8> {
+    let m = |i: usize| (a.0[i] as f32 * (1.0 - t) + b.0[i] as f32 * t) as u8;
+    Rgba([m(0), m(1), m(2), 255])
+}
+
+impl Scene {
+    fn new(clip: &Clip, preset: Preset) -> Self {
+        let fonts = Fonts::load();
+        let art = clip.art_url.as_deref().and_then(draw::load_art);
+        let palette = art.as_ref().map(Palette::from_art).unwrap_or_default();
+        let (width, height) = size(preset);
+        let unit = width.min(height) as f32;
+        let margin = unit * 0.06;
+
+        let mut base = RgbaImage::new(width, height);
+        draw::fill_gradient(&mut base, palette.top, palette.bottom);
+
+        // header: small cover with title and artist beside it, like the TUI
+        let thumb = unit * 0.16;
+        let mut text_x = margin;
+        if let Some(art) = &art {
+            draw::paste_art(&mut base, art, margin as u32, margin as u32, thumb as u32);
+            text_x += thumb + margin * 0.5;
+        }
+        let text_w = width as f32 - text_x - margin;
+        let (title_px, artist_px) = (thumb * 0.26, thumb * 0.2);
+        let title = draw::wrap(&fonts.bold, title_px, &clip.title, text_w);
+        let artist = draw::wrap(&fonts.regular, artist_px, &clip.artist, text_w);
+        let title = &title[..title.len().min(1)];
+        let artist = &artist[..artist.len().min(1)];
+        let header_h = draw::line_height(&fonts.bold, title_px)
+            + draw::line_height(&fonts.regular, artist_px);
+        let (bold, regular) = (&fonts.bold, &fonts.regular);
+        let y = margin + (thumb - header_h).max(0.0) / 2.0;
+        let y = draw::lines(&mut base, bold, title_px, text_x, y, text_w, Align::Left, title, palette.text);
+        draw::lines(&mut base, regular, artist_px, text_x, y, text_w, Align::Left, artist, palette.dim);
+
+        let top = (margin * 1.5 + thumb) as u32;
+        let panel = (margin as u32, top, width - 2 * margin as u32, height - top - margin as u32);
+
+        // measured in bold so the highlighted line never wraps differently
+        let px = unit * 0.055;
+        let step = draw::line_height(&fonts.bold, px);
+        let rows: Vec<Vec<String>> = clip
+            .lines
+            .iter()
+            .map(|l| match draw::wrap(&fonts.bold, px, &l.text, panel.2 as f32) {
+                rows if rows.is_empty() => vec![String::new()],
+                rows => rows,
+            })
+            .collect();
+        let heights: Vec<f32> = rows.iter().map(|r| r.len() as f32 * step + px * 0.5).collect();
+        let tops = heights
+            .iter()
+            .scan(0.0, |y, h| {
+                let top = *y;
+                *y += h;
+                Some(top)
+            })
+            .collect();
+
+        Self {
+            faint: mix(palette.dim, palette.bottom, 0.5),
+            fonts,
+            palette,
+            base,
+            panel,
+            px,
+            rows,
+            tops,
+            heights,
+        }
+    }
+
+    // column y that sits in the middle of the panel when `current` is shown
+    fn center(&self, current: Option<usize>) -> f32 {
+        let i = current.unwrap_or(0);
+        self.tops.get(i).map_or(0.0, |t| t + self.heights[i] / 2.0)
+    }
+
+    fn frame(&self, current: Option<usize>, scroll: f32) -> RgbaImage {
+        let (x, y, w, h) = self.panel;
+        // drawn on a crop so lines scrolling out never overlap the header
+        let mut panel = image::imageops::crop_imm(&self.base, x, y, w, h).to_image();
+        let shift = h as f32 / 2.0 - scroll;
+        for (i, rows) in self.rows.iter().enumerate() {
+            let top = self.tops[i] + shift;
+            if top + self.heights[i] < 0.0 || top > h as f32 {
+                continue;
+            }
+            let d = current.map_or(usize::MAX, |c| c.abs_diff(i));
+            let (font, color) = match d {
+                0 => (&self.fonts.bold, self.palette.text),
+                1..=2 => (&self.fonts.regular, self.palette.dim),
+                _ => (&self.fonts.regular, self.faint),
+            };
+            draw::lines(&mut panel, font, self.px, 0.0, top, w as f32, Align::Center, rows, color);
+        }
+        let mut frame = self.base.clone();
+        image::imageops::overlay(&mut frame, &panel, x as i64, y as i64);
+        frame
+    }
+}
+
+/// What each frame shows: highlighted line, scroll position and how long
+/// it stays up.
+fn plan(clip: &Clip, scene: &Scene) -> Vec<(Option<usize>, f32, u64)> {
+    let length = clip.to_ms - clip.from_ms;
+    let start = (clip.from_ms as i64 + clip.offset_ms).max(0) as u64;
+    let mut changes = vec![(0, lyrics::current_line_index(&clip.lines, start))];
+    for (i, line) in clip.lines.iter().enumerate() {
+        // song time at which this line becomes current
+        let at = (line.time_ms as i64 - clip.offset_ms).max(0) as u64;
+        if at <= clip.from_ms || at >= clip.to_ms {
+            continue;
+        }
+        let at = at - clip.from_ms;
+        match changes.last_mut() {
+            Some(last) if last.0 == at => last.1 = Some(i),
+            _ => changes.push((at, Some(i))),
+        }
+    }
+
+    let mut frames = Vec::new();
+    for (k, &(at, current)) in changes.iter().enumerate() {
+        let end = changes.get(k + 1).map_or(length, |c| c.0);
+        let mut hold = end.saturating_sub(at);
+        let to = scene.center(current);
+        if k > 0 && hold >= 2 * SCROLL_MS {
+            let from = scene.center(changes[k - 1].1);
+            let step = SCROLL_MS / SCROLL_FRAMES;
+            for n in 1..SCROLL_FRAMES {
+                let t = n as f32 / SCROLL_FRAMES as f32;
+                let eased = 1.0 - (1.0 - t) * (1.0 - t);
+                frames.push((current, from + (to - from) * eased, step));
+                hold -= step;
+            }
+        }
+        frames.push((current, to, hold));
+    }
+    frames
+}
+
+fn write_gif(output: &str, scene: &Scene, frames: &[(Option<usize>, f32, u64)]) -> Result<(), String> {
+    let file = File::create(output).map_err(|e| format!("failed to create {output}: {e}"))?;
+    let mut encoder = GifEncoder::new_with_speed(BufWriter::new(file), 10);
+    encoder.set_repeat(Repeat::Infinite).map_err(|e| e.to_string())?;
+    for &(current, scroll, ms) in frames {
+        let delay = Delay::from_numer_denom_ms(ms as u32, 1);
+        encoder
+            .encode_frame(Frame::from_parts(scene.frame(current, scroll), 0, 0, delay))
+            .map_err(|e| format!("failed to write {output}: {e}"))?;
+    }
+    Ok(())
+}
+
+fn write_apng(output: &str, scene: &Scene, frames: &[(Option<usize>, f32, u64)]) -> Result<(), String> {
+    let file = File::create(output).map_err(|e| format!("failed to create {output}: {e}"))?;
+    let err = |e: png::EncodingError| format!("failed to write {output}: {e}");
+    let (width, height) = scene.base.dimensions();
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, 0).map_err(err)?;
+    let mut writer = encoder.write_header().map_err(err)?;
+    for &(current, scroll, ms) in frames {
+        // centiseconds, like GIF, so long holds still fit in a u16
+        writer.set_frame_delay((ms / 10).min(u16::MAX as u64) as u16, 100).map_err(err)?;
+        writer.write_image_data(scene.frame(current, scroll).as_raw()).map_err(err)?;
+    }
+    writer.finish().map_err(err)
+}
+
+/// Renders `clip` as an animated GIF or APNG.
+pub fn render(clip: &Clip, preset: Preset, format: Format, output: &str) -> Result<usize, String> {
+    let scene = Scene::new(clip, preset);
+    let frames = plan(clip, &scene);
+    match format {
+        Format::Gif => write_gif(output, &scene, &frames)?,
+        Format::Apng => write_apng(output, &scene, &frames)?,
+    }
+    Ok(frames.len())
+}
+
+fn current(from_ms: u64, to_ms: Option<u64>, offset_ms: i64) -> Result<Clip, String> {
+    let state = client::fetch_state();
+    let offset_ms = offset_ms + state.offset_ms();
+    let np = state.now_playing.ok_or("nothing playing right now")?;
+    let ly = state.lyrics.ok_or("no lyrics found")?;
+    if !ly.synced {
+        return Err("lyrics aren't synced, nothing to time the frames to".into());
+    }
+    let mut to_ms = to_ms.unwrap_or(from_ms + 30_000);
+    if np.duration_ms > 0 {
+        to_ms = to_ms.min(np.duration_ms);
+    }
+    if from_ms >= to_ms {
+        return Err("--from must be before --to".into());
+    }
+    // out-of-order LRC files are served as they are; frames are timed in order
+    let mut lines = ly.lines;
+    lines.sort_by_key(|l| l.time_ms);
+    Ok(Clip {
+        lines,
+        artist: np.artist,
+        title: np.track,
+        art_url: np.album_art,
+        from_ms,
+        to_ms,
+        offset_ms,
+    })
+}
+
+/// `lirik video`: the current track between `from_ms` and `to_ms`
+/// (default 30s) as an animated GIF or APNG.
+pub fn run(
+    from_ms: u64,
+    to_ms: Option<u64>,
+    preset: Preset,
+    output: &str,
+    offset_ms: i64,
+) -> Result<(), String> {
+    let format = Format::from_path(output)
+        .ok_or_else(|| format!("can't tell the format of {output} (use .gif or .png)"))?;
+    let clip = current(from_ms, to_ms, offset_ms)?;
+    let frames = render(&clip, preset, format, output)?;
+    eprintln!("wrote {output} ({frames} frames)");
+    Ok(())
+}
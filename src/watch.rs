@@ -14,7 +14,7 @@ pub fn run(json: bool, offset_ms: i64) {
             last_fetch = Instant::now();
         }
 
-        let Some(np) = client::estimate(&state) else {
+        let Some(np) = state.estimate() else {
             if was_playing {
                 was_playing = false;
                 println!();
@@ -52,18 +52,19 @@ pub fn run(json: bool, offset_ms: i64) {
             }
         }
 
-        if let Some(ref ly) = state.lyrics {
-            if ly.synced {
-                let adjusted = (np.progress_ms as i64 + offset_ms).max(0) as u64;
-                if let Some(idx) = lyrics::current_line_index(&ly.lines, adjusted) {
-                    if last_idx != Some(idx) {
-                        last_idx = Some(idx);
-                        if json {
-                            println!("{}", serde_json::to_string(&ly.lines[idx]).unwrap());
-                        } else {
-                            println!("{}", ly.lines[idx].text);
-                        }
-                    }
+        if let Some(ref ly) = state.lyrics
+            && ly.synced
+        {
+            let offset = offset_ms + state.offset_ms();
+            let adjusted = (np.progress_ms as i64 + offset).max(0) as u64;
+            if let Some(idx) = lyrics::current_line_index(&ly.lines, adjusted)
+                && last_idx != Some(idx)
+            {
+                last_idx = Some(idx);
+                if json {
+                    println!("{}", serde_json::to_string(&ly.lines[idx]).unwrap());
+                } else {
+                    println!("{}", ly.lines[idx].text);
                 }
             }
         }
@@ -1,5 +1,5 @@
+use crate::player::PlayerBackend;
 use crate::spotify::State;
-use rspotify::AuthCodeSpotify;
 use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
@@ -7,7 +7,7 @@ use tokio::sync::RwLock;
 
 const PAGE: &str = include_str!("web.html");
 
-pub async fn serve(port: u16, state: Arc<RwLock<State>>, client: Arc<AuthCodeSpotify>) {
+pub async fn serve(port: u16, state: Arc<RwLock<State>>, player: Arc<dyn PlayerBackend>) {
     let listener = match TcpListener::bind(format!("0.0.0.0:{port}")).await {
         Ok(l) => l,
         Err(e) => {
@@ -23,7 +23,7 @@ pub async fn serve(port: u16, state: Arc<RwLock<State>>, client: Arc<AuthCodeSpo
             continue;
         };
         let state = state.clone();
-        let client = client.clone();
+        let player = player.clone();
         tokio::spawn(async move {
             let mut buf = [0u8; 4096];
             let n = stream.read(&mut buf).await.unwrap_or(0);
@@ -38,6 +38,12 @@ pub async fn serve(port: u16, state: Arc<RwLock<State>>, client: Arc<AuthCodeSpo
             let (status, ctype, body) = if path == "/api/state" {
                 let s = state.read().await;
                 ("200 OK", "application/json", serde_json::to_string(&*s).unwrap())
+            } else if path == "/api/queue" {
+                let body = match player.queue().await {
+                    Ok(q) => serde_json::to_string(&q).unwrap(),
+                    Err(e) => serde_json::json!({"error": e}).to_string(),
+                };
+                ("200 OK", "application/json", body)
             } else if path == "/api/cmd" && method == "POST" {
                 // extract JSON body after \r\n\r\n
                 let req_str = req.to_string();
@@ -50,7 +56,7 @@ pub async fn serve(port: u16, state: Arc<RwLock<State>>, client: Arc<AuthCodeSpo
                     Ok(v) => {
                         let cmd = v["cmd"].as_str().unwrap_or("");
                         let arg = v["arg"].as_str();
-                        match crate::daemon::execute_cmd(&client, &state, cmd, arg).await {
+                        match crate::daemon::execute_cmd(&*player, &state, cmd, arg).await {
                             Ok(()) => r#"{"ok":true}"#.to_string(),
                             Err(e) => {
                                 let e = e.replace('"', r#"\""#);